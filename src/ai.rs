@@ -0,0 +1,353 @@
+use crate::simulation::{PaddleInput, SimBall, Simulation, ARENA_HEIGHT, TICK_DELTA};
+use crate::utils::{asset_path, Ini, Rng};
+use std::fs;
+
+const DIFFICULTY_FILE: &str = "ai/difficulty.ini";
+// Shipped presets, used when the file in the assets folder is missing or broken.
+const DEFAULT_DIFFICULTY: &str = include_str!("../assets/ai/difficulty.ini");
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AiDifficulty {
+    Easy,
+    Normal,
+    Hard,
+    Impossible,
+}
+impl AiDifficulty {
+    pub const ALL: [AiDifficulty; 4] = [
+        AiDifficulty::Easy,
+        AiDifficulty::Normal,
+        AiDifficulty::Hard,
+        AiDifficulty::Impossible,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AiDifficulty::Easy => "easy",
+            AiDifficulty::Normal => "normal",
+            AiDifficulty::Hard => "hard",
+            AiDifficulty::Impossible => "impossible",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AiDifficulty> {
+        AiDifficulty::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.name() == name)
+    }
+}
+
+/// Tuning values of a computer opponent, see `assets/ai/difficulty.ini`.
+#[derive(Clone, Debug)]
+pub struct AiPreset {
+    pub reaction_time: f32,
+    pub max_speed: f32,
+    pub prediction_noise: f32,
+    pub hesitation: f32,
+}
+
+/// All difficulty presets, indexed like `AiDifficulty::ALL`.
+pub struct AiPresets {
+    presets: Vec<AiPreset>,
+}
+impl AiPresets {
+    /// Loads the presets from the assets folder, falling back to the shipped ones.
+    pub fn load() -> AiPresets {
+        let path = asset_path(DIFFICULTY_FILE);
+        let loaded = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| AiPresets::parse(&source));
+        match loaded {
+            Ok(presets) => presets,
+            Err(error) => {
+                eprintln!(
+                    "Could not load AI presets from {}: {}",
+                    path.display(),
+                    error
+                );
+                AiPresets::default()
+            }
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<AiPresets, String> {
+        let ini = Ini::parse(source).map_err(|error| error.to_string())?;
+        let presets = AiDifficulty::ALL
+            .iter()
+            .map(|difficulty| {
+                let section = ini
+                    .section(difficulty.name())
+                    .ok_or_else(|| format!("missing preset [{}]", difficulty.name()))?;
+                Ok(AiPreset {
+                    reaction_time: section.parse("reaction_time")?,
+                    max_speed: section.parse("max_speed")?,
+                    prediction_noise: section.parse("prediction_noise")?,
+                    hesitation: section.parse("hesitation")?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(AiPresets { presets })
+    }
+
+    pub fn get(&self, difficulty: AiDifficulty) -> &AiPreset {
+        &self.presets[difficulty as usize]
+    }
+}
+impl Default for AiPresets {
+    fn default() -> Self {
+        AiPresets::parse(DEFAULT_DIFFICULTY).expect("shipped AI presets are valid")
+    }
+}
+
+/// Attach to a `Paddle` entity to have it controlled by the computer instead of the keyboard.
+pub struct AiController {
+    pub preset: AiPreset,
+    rng: Rng,
+    reaction_timer: f32,
+    target_y: Option<f32>,
+    aim_error: f32,
+}
+impl AiController {
+    pub fn new(preset: AiPreset, rng: Rng) -> AiController {
+        AiController {
+            preset,
+            rng,
+            reaction_timer: 0.0,
+            target_y: None,
+            aim_error: 0.0,
+        }
+    }
+
+    /// Moves the paddle towards the point where the closest incoming ball will cross `face_x`
+    /// and returns the new (unclamped) paddle position. `walls` are the bottom and top walls,
+    /// see `predict_crossing_y`.
+    ///
+    /// The target is only re-evaluated every `reaction_time` seconds, in between the paddle
+    /// keeps chasing the last known target.
+    pub fn update(
+        &mut self,
+        delta: f32,
+        face_x: f32,
+        paddle_y: f32,
+        balls: &[SimBall],
+        walls: (Option<f32>, Option<f32>),
+    ) -> f32 {
+        self.reaction_timer -= delta;
+        if self.reaction_timer <= 0.0 {
+            self.reaction_timer = self.preset.reaction_time;
+
+            let target_y = balls
+                .iter()
+                .filter_map(|ball| {
+                    // The ball touches the paddle once its edge, not its center, reaches the face.
+                    let contact_x = if ball.position[0] < face_x {
+                        face_x - ball.radius
+                    } else {
+                        face_x + ball.radius
+                    };
+                    predict_crossing_y(ball, contact_x, walls)
+                        .map(|y| ((contact_x - ball.position[0]).abs(), y))
+                })
+                .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap())
+                .map(|(_, y)| y);
+
+            // Misjudge each incoming ball by a fixed amount instead of jittering on every look.
+            if self.target_y.is_none() && target_y.is_some() {
+                let noise = self.preset.prediction_noise;
+                self.aim_error = self.rng.range(-noise, noise);
+            }
+            self.target_y = target_y;
+        }
+
+        let (target_y, speed) = match self.target_y {
+            Some(target_y) => (target_y + self.aim_error, self.preset.max_speed),
+            // Without an incoming ball we drift back to the center to cover as much as possible.
+            None => (
+                ARENA_HEIGHT / 2.0,
+                self.preset.max_speed * (1.0 - self.preset.hesitation),
+            ),
+        };
+        let max_step = speed * delta;
+        paddle_y + (target_y - paddle_y).min(max_step).max(-max_step)
+    }
+
+    /// Decides how to move `player`'s paddle during the next tick of `simulation`. Top and
+    /// bottom paddles look at the arena with x and y swapped, the arena is square.
+    pub fn input(&mut self, simulation: &Simulation, player: usize) -> PaddleInput {
+        let paddle = &simulation.paddles[player];
+        let offset = paddle.offset();
+        let walls = simulation.walls();
+        let target = if paddle.side.is_horizontal() {
+            let balls = simulation
+                .balls
+                .iter()
+                .map(|ball| SimBall {
+                    position: [ball.position[1], ball.position[0]],
+                    previous_position: [ball.previous_position[1], ball.previous_position[0]],
+                    velocity: [ball.velocity[1], ball.velocity[0]],
+                    radius: ball.radius,
+                })
+                .collect::<Vec<_>>();
+            let walls = (walls.left, walls.right);
+            self.update(TICK_DELTA, paddle.face(), offset, &balls, walls)
+        } else {
+            let walls = (walls.bottom, walls.top);
+            self.update(TICK_DELTA, paddle.face(), offset, &simulation.balls, walls)
+        };
+        PaddleInput::towards(offset, target)
+    }
+}
+
+/// Predicts the y coordinate at which the ball center reaches `target_x`, taking bounces off
+/// the bottom and top `walls` into account. A border without a wall is an open goal. Returns
+/// `None` if the ball is moving away or goes into a goal first.
+pub fn predict_crossing_y(
+    ball: &SimBall,
+    target_x: f32,
+    walls: (Option<f32>, Option<f32>),
+) -> Option<f32> {
+    let [x, y] = ball.position;
+    let [velocity_x, velocity_y] = ball.velocity;
+    if velocity_x == 0.0 {
+        return None;
+    }
+
+    let time = (target_x - x) / velocity_x;
+    if time < 0.0 {
+        return None;
+    }
+
+    // Where the ball center turns around at the walls.
+    let bottom = walls.0.map(|wall| wall + ball.radius);
+    let top = walls.1.map(|wall| wall - ball.radius);
+    let unfolded = y + velocity_y * time;
+    if let (Some(bottom), Some(top)) = (bottom, top) {
+        // Unfold the bounces: the ball travels freely inside a band of height `span`, every
+        // time it leaves the band it is mirrored back in.
+        let span = top - bottom;
+        let travelled = (unfolded - bottom).rem_euclid(2.0 * span);
+        let folded = if travelled > span {
+            2.0 * span - travelled
+        } else {
+            travelled
+        };
+        return Some(bottom + folded);
+    }
+
+    // With a goal on one side the ball bounces at most once before it is out.
+    let crossing = match (bottom, top) {
+        (Some(bottom), _) if unfolded < bottom => 2.0 * bottom - unfolded,
+        (_, Some(top)) if unfolded > top => 2.0 * top - unfolded,
+        _ => unfolded,
+    };
+    if crossing > ball.radius && crossing < ARENA_HEIGHT - ball.radius {
+        Some(crossing)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{
+        clamp_paddle_y, ARENA_WIDTH, BALL_RADIUS, BALL_VELOCITY_X, BALL_VELOCITY_Y, PADDLE_HEIGHT,
+        PADDLE_WIDTH,
+    };
+
+    #[test]
+    fn shipped_presets_are_valid() {
+        let presets = AiPresets::default();
+        for difficulty in AiDifficulty::ALL.iter() {
+            assert!(presets.get(*difficulty).max_speed > 0.0);
+        }
+    }
+
+    #[test]
+    fn open_goals_end_the_prediction() {
+        let position = [50.0, 50.0];
+        let ball = SimBall {
+            position,
+            previous_position: position,
+            velocity: [50.0, 50.0],
+            radius: BALL_RADIUS,
+        };
+        let walls = (Some(0.0), Some(ARENA_HEIGHT));
+        // A second of flight takes the ball to y 100, it bounces off the top on the way.
+        let bounced = 2.0 * (ARENA_HEIGHT - BALL_RADIUS) - 100.0;
+        let predicted = predict_crossing_y(&ball, 100.0, walls).unwrap();
+        assert!((predicted - bounced).abs() < 1e-3, "{}", predicted);
+
+        // The ball goes into the top goal before it gets to the right.
+        assert_eq!(predict_crossing_y(&ball, 100.0, (Some(0.0), None)), None);
+        assert_eq!(
+            predict_crossing_y(&ball, 70.0, (Some(0.0), None)),
+            Some(70.0)
+        );
+        let predicted = predict_crossing_y(&ball, 100.0, (None, Some(ARENA_HEIGHT))).unwrap();
+        assert!((predicted - bounced).abs() < 1e-3, "{}", predicted);
+    }
+
+    #[test]
+    fn impossible_preset_never_misses() {
+        let preset = AiPresets::default().get(AiDifficulty::Impossible).clone();
+        let delta = 1.0 / 60.0;
+        let paddle_x = ARENA_WIDTH - PADDLE_WIDTH * 0.5;
+        let face_x = paddle_x - PADDLE_WIDTH * 0.5;
+
+        for start in 0..20 {
+            for &velocity_y in &[
+                -BALL_VELOCITY_Y,
+                -BALL_VELOCITY_Y * 0.5,
+                0.0,
+                BALL_VELOCITY_Y * 0.5,
+                BALL_VELOCITY_Y,
+            ] {
+                for &paddle_start in &[PADDLE_HEIGHT * 0.5, ARENA_HEIGHT / 2.0, ARENA_HEIGHT] {
+                    // Serve from the opposite paddle at various heights.
+                    let position = [PADDLE_WIDTH + BALL_RADIUS, 2.0 + start as f32 * 4.8];
+                    let mut ball = SimBall {
+                        position,
+                        previous_position: position,
+                        velocity: [BALL_VELOCITY_X, velocity_y],
+                        radius: BALL_RADIUS,
+                    };
+                    let mut paddle_y = clamp_paddle_y(paddle_start, PADDLE_HEIGHT);
+                    let mut ai = AiController::new(preset.clone(), Rng::new(start));
+
+                    while ball.position[0] < face_x - BALL_RADIUS {
+                        paddle_y = clamp_paddle_y(
+                            ai.update(
+                                delta,
+                                face_x,
+                                paddle_y,
+                                std::slice::from_ref(&ball),
+                                (Some(0.0), Some(ARENA_HEIGHT)),
+                            ),
+                            PADDLE_HEIGHT,
+                        );
+
+                        ball.position[0] += ball.velocity[0] * delta;
+                        ball.position[1] += ball.velocity[1] * delta;
+                        if (ball.position[1] <= ball.radius && ball.velocity[1] < 0.0)
+                            || (ball.position[1] >= ARENA_HEIGHT - ball.radius
+                                && ball.velocity[1] > 0.0)
+                        {
+                            ball.velocity[1] = -ball.velocity[1];
+                        }
+                    }
+
+                    assert!(
+                        (ball.position[1] - paddle_y).abs() <= PADDLE_HEIGHT * 0.5 + BALL_RADIUS,
+                        "missed ball at y {} with paddle at {} (start {}, velocity {})",
+                        ball.position[1],
+                        paddle_y,
+                        start,
+                        velocity_y
+                    );
+                }
+            }
+        }
+    }
+}
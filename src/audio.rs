@@ -1,45 +1,396 @@
-use bevy::asset::{AssetServer, Handle};
-use bevy::audio::{Audio, AudioSource};
-use bevy::ecs::{Commands, Res};
-use std::iter::Cycle;
-use std::vec::IntoIter;
+use bevy::app::{EventReader, Events};
+use bevy::core::Time;
+use bevy::ecs::{Commands, Local, Res, ResMut};
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, OutputStream, Source};
+use simple_pong_bevy::events::{BallHitPaddle, BallHitWall, PointScored};
+use simple_pong_bevy::manifest::{SoundManifest, TrackEntry};
+use simple_pong_bevy::mixer::{Bus, Mixer};
+use simple_pong_bevy::music::{ogg_duration, Playlist};
+use simple_pong_bevy::sound_theme::{Playback, SoundTheme, SoundThemes};
+use simple_pong_bevy::utils::{asset_path, Rng};
+use std::fs;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
-const BOUNCE_SOUND: &str = "audio/bounce.ogg";
-const SCORE_SOUND: &str = "audio/score.ogg";
+/// The effect in the sound manifest that plays when a point is scored.
+const SCORE_EFFECT: &str = "score";
 
-const MUSIC_TRACKS: &[&str] = &[
-    "audio/Computer_Music_All-Stars_-_Wheres_My_Jetpack.ogg",
-    "audio/Computer_Music_All-Stars_-_Albatross_v2.ogg",
-];
+/// Seconds the end of a track overlaps with the start of the next one, at most half the track.
+const CROSSFADE_SECONDS: f32 = 3.0;
 
+/// The sound effects, `None` if their file could not be loaded.
 pub struct Sounds {
-    pub score_sfx: Handle<AudioSource>,
-    pub bounce_sfx: Handle<AudioSource>,
+    pub score_sfx: Option<Arc<[u8]>>,
+    pub paddle_sfx: Option<Arc<[u8]>>,
+    pub wall_sfx: Option<Arc<[u8]>>,
+    /// How the paddle and wall sounds follow the ball.
+    pub theme: SoundTheme,
 }
 
+/// Source of the random pitch changes of the collision sounds.
+pub struct HitVariation(Rng);
+impl Default for HitVariation {
+    fn default() -> Self {
+        HitVariation(Rng::from_time())
+    }
+}
+
+/// Shared between a playing `Voice` and the game, which adjusts the voice while the audio
+/// thread plays it.
+pub struct VoiceControl {
+    /// Bits of the `f32` gain.
+    gain: AtomicU32,
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    finished: AtomicBool,
+    /// Samples played so far, counting every channel.
+    samples: AtomicU64,
+    samples_per_second: f32,
+}
+impl VoiceControl {
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// True once the voice played to its end or was stopped.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Seconds played so far.
+    pub fn position(&self) -> f32 {
+        self.samples.load(Ordering::Relaxed) as f32 / self.samples_per_second
+    }
+}
+
+/// A sound file being decoded for the output device, with its `VoiceControl` and `Playback`
+/// applied. Mono files come out in stereo so they can be panned.
+pub struct Voice {
+    decoder: Decoder<Cursor<Arc<[u8]>>>,
+    control: Arc<VoiceControl>,
+    /// Channels of the file.
+    channels: u16,
+    sample_rate: u32,
+    /// Gains of the left and right channel.
+    pan: [f32; 2],
+    pitch: f32,
+    /// The right channel of a mono sample, played after the left one.
+    pending: Option<f32>,
+    /// Samples left in a frame of silence while paused.
+    silence: u16,
+}
+impl Voice {
+    pub fn new(
+        bytes: Arc<[u8]>,
+        gain: f32,
+        playback: Playback,
+    ) -> Result<(Voice, Arc<VoiceControl>), DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let control = Arc::new(VoiceControl {
+            gain: AtomicU32::new(gain.to_bits()),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            samples: AtomicU64::new(0),
+            samples_per_second: f32::from(channels) * sample_rate as f32,
+        });
+        let voice = Voice {
+            decoder,
+            control: control.clone(),
+            channels,
+            sample_rate,
+            pan: playback.channel_gains(),
+            pitch: playback.pitch,
+            pending: None,
+            silence: 0,
+        };
+        Ok((voice, control))
+    }
+}
+impl Iterator for Voice {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.pending.take() {
+            return Some(sample);
+        }
+        if self.silence > 0 {
+            self.silence -= 1;
+            return Some(0.0);
+        }
+        let control = &self.control;
+        if control.stopped.load(Ordering::Relaxed) {
+            control.finished.store(true, Ordering::Relaxed);
+            return None;
+        }
+        // Pauses start on a frame boundary and last whole frames, so the channels stay in
+        // place.
+        let samples = control.samples.load(Ordering::Relaxed);
+        if control.paused.load(Ordering::Relaxed) && samples % u64::from(self.channels) == 0 {
+            self.silence = self.channels() - 1;
+            return Some(0.0);
+        }
+
+        match self.decoder.next() {
+            Some(sample) => {
+                control.samples.store(samples + 1, Ordering::Relaxed);
+                let sample = f32::from(sample) / 32768.0 * control.gain();
+                if self.channels == 1 {
+                    self.pending = Some(sample * self.pan[1]);
+                    return Some(sample * self.pan[0]);
+                }
+                // Channels beyond the first two are not panned.
+                let channel = (samples % u64::from(self.channels)) as usize;
+                Some(sample * self.pan.get(channel).copied().unwrap_or(1.0))
+            }
+            None => {
+                control.finished.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+impl Source for Voice {
+    // Ogg Vorbis files keep their channels and sample rate, frames never end.
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels.max(2)
+    }
+
+    // Claiming a different sample rate makes the output resample the voice, which changes its
+    // speed and pitch together.
+    fn sample_rate(&self) -> u32 {
+        (self.sample_rate as f32 * self.pitch).round().max(1.0) as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Plays voices on the default output device. The device's stream can't leave the thread that
+/// opened it, so it lives on a thread of its own that the voices are handed to.
+pub struct AudioPlayer {
+    voices: Mutex<Sender<Voice>>,
+}
+impl AudioPlayer {
+    pub fn open() -> AudioPlayer {
+        let (sender, receiver) = mpsc::channel::<Voice>();
+        thread::spawn(move || {
+            let (_stream, handle) = match OutputStream::try_default() {
+                Ok(output) => output,
+                Err(error) => {
+                    eprintln!("No audio output: {}", error);
+                    return;
+                }
+            };
+            for voice in receiver {
+                if let Err(error) = handle.play_raw(voice) {
+                    eprintln!("Could not play a sound: {}", error);
+                }
+            }
+        });
+        AudioPlayer {
+            voices: Mutex::new(sender),
+        }
+    }
+
+    /// Starts playing the sound file, `None` if it can't be decoded.
+    pub fn play(
+        &self,
+        bytes: &Arc<[u8]>,
+        gain: f32,
+        playback: Playback,
+    ) -> Option<Arc<VoiceControl>> {
+        let (voice, control) = match Voice::new(bytes.clone(), gain, playback) {
+            Ok(voice) => voice,
+            Err(error) => {
+                eprintln!("Could not decode a sound: {}", error);
+                return None;
+            }
+        };
+        // Without an output device the thread is gone and the voice is dropped unheard.
+        let _ = self.voices.lock().unwrap().send(voice);
+        Some(control)
+    }
+}
+
+pub struct MusicTrack {
+    pub title: String,
+    pub artist: String,
+    pub license: String,
+    bytes: Arc<[u8]>,
+    /// Seconds.
+    duration: f32,
+}
+
+struct PlayingTrack {
+    track: usize,
+    control: Arc<VoiceControl>,
+    /// Fades from zero to one when the track starts and back when it is replaced.
+    fade: f32,
+}
+
+/// The game's background music, played by `music_system` in the order of `playlist`.
 pub struct Music {
-    pub music: Cycle<IntoIter<Handle<AudioSource>>>,
+    pub tracks: Vec<MusicTrack>,
+    pub playlist: Playlist,
+    paused: bool,
+    /// Crossfade to the playlist's current track, it was changed from a menu.
+    switch: bool,
+    playing: Option<PlayingTrack>,
+    fading_out: Vec<PlayingTrack>,
+}
+impl Music {
+    /// The track that is playing, or about to.
+    pub fn now_playing(&self) -> Option<&MusicTrack> {
+        let track = match &self.playing {
+            Some(playing) if !self.switch => Some(playing.track),
+            _ => self.playlist.current(),
+        };
+        track.map(|track| &self.tracks[track])
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn skip(&mut self) {
+        self.playlist.skip();
+        self.switch = true;
+    }
+
+    pub fn back(&mut self) {
+        self.playlist.back();
+        self.switch = true;
+    }
+
+    fn start(&self, player: &AudioPlayer, track: usize) -> Option<PlayingTrack> {
+        player
+            .play(&self.tracks[track].bytes, 0.0, Playback::default())
+            .map(|control| PlayingTrack {
+                track,
+                control,
+                fade: 0.0,
+            })
+    }
+}
+
+/// Reads a whole sound file, the game decodes it again every time it is played. `entry` names
+/// the file's section in the sound manifest for the error message.
+fn load_audio_file(entry: &str, file: &str) -> Option<Arc<[u8]>> {
+    let path = asset_path(file);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!(
+                "Could not load {} from {}: {}",
+                entry,
+                path.display(),
+                error
+            );
+            return None;
+        }
+    };
+    if ogg_duration(&bytes).is_none() {
+        eprintln!(
+            "Could not load {} from {}: not Ogg Vorbis",
+            entry,
+            path.display()
+        );
+        return None;
+    }
+    Some(bytes.into())
 }
 
-fn load_audio_track(asset_server: &Res<AssetServer>, file: &str) -> Handle<AudioSource> {
-    asset_server.load(file)
+/// A missing or broken effect stays silent.
+fn load_effect(manifest: &SoundManifest, name: &str) -> Option<Arc<[u8]>> {
+    let entry = format!("[effect.{}]", name);
+    match manifest.effect(name) {
+        Some(effect) => load_audio_file(&entry, &effect.file),
+        None => {
+            eprintln!("Could not load {}: not in the sound manifest", entry);
+            None
+        }
+    }
 }
 
-// TODO play game music
+/// Broken music files are left out of the playlist.
+fn load_music_track(track: &TrackEntry) -> Option<MusicTrack> {
+    let bytes = load_audio_file(&format!("[music.{}]", track.name), &track.file)?;
+    let duration = ogg_duration(&bytes)?;
+    Some(MusicTrack {
+        title: track.title.clone(),
+        artist: track.artist.clone(),
+        license: track.license.clone(),
+        bytes,
+        duration,
+    })
+}
 
-pub fn initialise_audio(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+/// Loads the sounds listed in the sound manifest. `theme` names one of the sound themes, by
+/// default the first one plays.
+pub fn initialise_audio(commands: &mut Commands, theme: Option<&str>) {
     let (sound_effects, music) = {
-        let music = MUSIC_TRACKS
+        let manifest = SoundManifest::load();
+        let tracks = manifest
+            .tracks
             .iter()
-            .map(|file| load_audio_track(&asset_server, file))
-            .collect::<Vec<_>>()
-            .into_iter()
-            .cycle();
-        let music = Music { music };
+            .filter_map(load_music_track)
+            .collect::<Vec<_>>();
+        let music = Music {
+            playlist: Playlist::new(tracks.len(), Rng::from_time()),
+            tracks,
+            paused: false,
+            switch: false,
+            playing: None,
+            fading_out: Vec::new(),
+        };
 
+        let themes = SoundThemes::load();
+        let theme = match theme.map(|name| (name, themes.get(name))) {
+            Some((_, Some(theme))) => theme,
+            Some((name, None)) => {
+                eprintln!(
+                    "Unknown sound theme {}, playing {}",
+                    name,
+                    themes.first().name
+                );
+                themes.first()
+            }
+            None => themes.first(),
+        };
         let sound = Sounds {
-            bounce_sfx: load_audio_track(&asset_server, BOUNCE_SOUND),
-            score_sfx: load_audio_track(&asset_server, SCORE_SOUND),
+            score_sfx: load_effect(&manifest, SCORE_EFFECT),
+            paddle_sfx: load_effect(&manifest, &theme.paddle.effect),
+            wall_sfx: load_effect(&manifest, &theme.wall.effect),
+            theme: theme.clone(),
         };
 
         (sound, music)
@@ -47,13 +398,123 @@ pub fn initialise_audio(commands: &mut Commands, asset_server: &Res<AssetServer>
 
     commands
         .insert_resource(sound_effects)
-        .insert_resource(music);
+        .insert_resource(music)
+        .insert_resource(AudioPlayer::open());
+}
+
+/// Plays the music tracks one after another. Shortly before a track ends, or right away when
+/// another one is picked, the next track fades in while the old one fades out. Volume changes
+/// apply to the playing tracks right away.
+pub fn music_system(
+    player: Res<AudioPlayer>,
+    time: Res<Time>,
+    mixer: Res<Mixer>,
+    mut music: ResMut<Music>,
+) {
+    let music = &mut *music;
+    for playing in music.playing.iter().chain(music.fading_out.iter()) {
+        playing.control.set_paused(music.paused);
+    }
+    if music.paused {
+        return;
+    }
+
+    let ending = match &music.playing {
+        Some(playing) => {
+            let duration = music.tracks[playing.track].duration;
+            // Short tracks overlap by less, or the next one would start right away.
+            let crossfade = CROSSFADE_SECONDS.min(duration / 2.0);
+            playing.control.is_finished() || playing.control.position() >= duration - crossfade
+        }
+        None => false,
+    };
+    if music.switch || ending {
+        let next = if music.switch {
+            music.playlist.current()
+        } else {
+            music.playlist.advance()
+        };
+        music.switch = false;
+        music.fading_out.extend(music.playing.take());
+        music.playing = next.and_then(|track| music.start(&player, track));
+    } else if music.playing.is_none() {
+        // Nothing plays yet right after startup.
+        if let Some(track) = music.playlist.current() {
+            music.playing = music.start(&player, track);
+        }
+    }
+
+    let step = time.delta_seconds() / CROSSFADE_SECONDS;
+    let gain = mixer.gain(Bus::Music);
+    if let Some(playing) = &mut music.playing {
+        playing.fade = (playing.fade + step).min(1.0);
+        playing.control.set_gain(playing.fade * gain);
+    }
+    for playing in &mut music.fading_out {
+        playing.fade = (playing.fade - step).max(0.0);
+        playing.control.set_gain(playing.fade * gain);
+        if playing.fade <= 0.0 {
+            playing.control.stop();
+        }
+    }
+    music
+        .fading_out
+        .retain(|playing| playing.fade > 0.0 && !playing.control.is_finished());
+}
+
+fn play_effect(player: &AudioPlayer, sound: Option<&Arc<[u8]>>, playback: Playback, mixer: &Mixer) {
+    let gain = mixer.gain(Bus::Effects);
+    if let Some(bytes) = sound {
+        if gain > 0.0 {
+            player.play(bytes, gain, playback);
+        }
+    }
+}
+
+/// The paddle sound as it plays in the middle of the arena.
+pub fn play_bounce_sound(player: &AudioPlayer, sounds: &Sounds, mixer: &Mixer) {
+    play_effect(
+        player,
+        sounds.paddle_sfx.as_ref(),
+        Playback::default(),
+        mixer,
+    );
 }
 
-pub fn play_bounce_sound(audio: &Res<Audio>, sounds: &Res<Sounds>) {
-    audio.play((*sounds).bounce_sfx.clone());
+pub fn play_score_sound(player: &AudioPlayer, sounds: &Sounds, mixer: &Mixer) {
+    play_effect(
+        player,
+        sounds.score_sfx.as_ref(),
+        Playback::default(),
+        mixer,
+    );
 }
 
-pub fn play_score_sound(audio: &Res<Audio>, sounds: &Res<Sounds>) {
-    audio.play((*sounds).score_sfx.clone());
+/// Plays the sound effect of every game event. Collision sounds follow the ball as the theme
+/// says, points play a jingle.
+pub fn sound_effects_system(
+    mut paddle_hit_reader: Local<EventReader<BallHitPaddle>>,
+    mut wall_hit_reader: Local<EventReader<BallHitWall>>,
+    mut point_reader: Local<EventReader<PointScored>>,
+    mut variation: Local<HitVariation>,
+    paddle_hits: Res<Events<BallHitPaddle>>,
+    wall_hits: Res<Events<BallHitWall>>,
+    points: Res<Events<PointScored>>,
+    player: Res<AudioPlayer>,
+    sounds: Res<Sounds>,
+    mixer: Res<Mixer>,
+) {
+    let theme = &sounds.theme;
+    let rng = &mut variation.0;
+    for hit in paddle_hit_reader.iter(&paddle_hits) {
+        let playback = theme.hit(&theme.paddle, hit.position, hit.speed, rng);
+        play_effect(&player, sounds.paddle_sfx.as_ref(), playback, &mixer);
+    }
+    for hit in wall_hit_reader.iter(&wall_hits) {
+        let playback = theme.hit(&theme.wall, hit.position, hit.speed, rng);
+        play_effect(&player, sounds.wall_sfx.as_ref(), playback, &mixer);
+    }
+    for _ in point_reader.iter(&points) {
+        play_score_sound(&player, &sounds, &mixer);
+    }
 }
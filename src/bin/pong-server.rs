@@ -0,0 +1,138 @@
+//! Dedicated server for online matches. Runs the authoritative match at a fixed tick and sends
+//! its state to two players and any number of spectators. It only uses the game's library, so
+//! it runs without a window, audio or GPU, e.g. in a container:
+//!
+//! `pong-server --port=7777 --points=5`
+
+use simple_pong_bevy::net::{MatchServer, Message};
+use simple_pong_bevy::physics::SpeedUp;
+use simple_pong_bevy::rules::{parse_setting, MatchRules};
+use simple_pong_bevy::simulation::{SimEvent, TICK_DELTA};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_PORT: u16 = 7777;
+const MAX_PACKET_SIZE: usize = 1500;
+/// A server that fell further behind than this skips the missed ticks instead of racing
+/// through them.
+const MAX_LAG: Duration = Duration::from_secs(1);
+
+fn main() {
+    let mut port = DEFAULT_PORT;
+    let mut rules = MatchRules::default();
+    let mut speed_up = SpeedUp::default();
+    for arg in std::env::args().skip(1) {
+        let (name, value) = match arg.find('=') {
+            Some(separator) => (&arg[..separator], Some(&arg[separator + 1..])),
+            None => (arg.as_str(), None),
+        };
+        if let Some(result) = parse_setting(&mut rules, &mut speed_up, name, value) {
+            if let Err(error) = result {
+                eprintln!("{}", error);
+            }
+            continue;
+        }
+        match (name, value) {
+            ("--port", Some(value)) => match value.parse() {
+                Ok(value) => port = value,
+                Err(_) => eprintln!("Invalid port {}", value),
+            },
+            _ => eprintln!("Ignoring unknown argument {}", arg),
+        }
+    }
+
+    if let Err(error) = run(port, rules, speed_up) {
+        eprintln!("pong-server: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(port: u16, rules: MatchRules, speed_up: SpeedUp) -> io::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_nonblocking(true)?;
+    println!("Listening on {}", socket.local_addr()?);
+
+    let mut server = MatchServer::new(rules, speed_up);
+    let tick = Duration::from_secs_f32(TICK_DELTA);
+    let mut next_tick = Instant::now();
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        let clients = server.clients().collect::<Vec<_>>();
+
+        loop {
+            let (length, from) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                // Some systems report a client that went away on the next read.
+                Err(error) if error.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(error) => return Err(error),
+            };
+            if let Some(message) = Message::from_bytes(&buffer[..length]) {
+                if let Some(answer) = server.receive(from, message) {
+                    send(&socket, &answer.to_bytes(), from)?;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if now.duration_since(next_tick) > MAX_LAG {
+            next_tick = now;
+        }
+        while next_tick <= now {
+            let state = server.tick();
+            if let Message::State { events, .. } = &state {
+                log_events(&server, events);
+            }
+            let bytes = state.to_bytes();
+            for client in server.clients() {
+                send(&socket, &bytes, client)?;
+            }
+            next_tick += tick;
+        }
+
+        log_clients(&server, &clients);
+        thread::sleep(next_tick.saturating_duration_since(Instant::now()));
+    }
+}
+
+fn send(socket: &UdpSocket, packet: &[u8], to: SocketAddr) -> io::Result<()> {
+    match socket.send_to(packet, to) {
+        Ok(_) => Ok(()),
+        // Like any other lost packet, the next state follows a tick later.
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+fn log_events(server: &MatchServer<SocketAddr>, events: &[SimEvent]) {
+    let simulation = server.simulation();
+    for event in events {
+        match event {
+            SimEvent::Scored(_) => println!(
+                "Score: | {:^3} | {:^3} |",
+                simulation.score_left, simulation.score_right
+            ),
+            SimEvent::MatchOver(side) => println!("{:?} player wins", side),
+            _ => {}
+        }
+    }
+}
+
+fn log_clients(server: &MatchServer<SocketAddr>, before: &[SocketAddr]) {
+    for client in server.clients() {
+        if !before.contains(&client) {
+            match server.side_of(client) {
+                Some(side) => println!("{} joined, playing {:?}", client, side),
+                None => println!("{} joined as a spectator", client),
+            }
+        }
+    }
+    for client in before {
+        if !server.clients().any(|remaining| remaining == *client) {
+            println!("{} left", client);
+        }
+    }
+}
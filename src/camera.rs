@@ -1,6 +1,6 @@
 use bevy::app::{EventReader, Events};
 use bevy::ecs::{Local, Query, QuerySet, Res};
-use bevy::prelude::{Added, Entity, Transform, Vec3};
+use bevy::prelude::{Added, Entity, Transform, Vec2, Vec3};
 use bevy::render::camera::Camera;
 use bevy::window::{Window, WindowCreated, WindowResized, Windows};
 
@@ -78,6 +78,25 @@ pub fn camera_virtual_screen_system(
     }
 }
 
+/// Converts a cursor or touch position in window pixels (origin in the bottom left) into the
+/// world coordinates seen by a camera with this `VirtualScreen`. For the game camera these are
+/// arena coordinates. With `ScaleType::Fit` positions in the bars around the arena end up
+/// outside of it.
+pub fn screen_to_world(
+    config: &VirtualScreen,
+    window: &Window,
+    camera_transform: &Transform,
+    position: Vec2,
+) -> Vec2 {
+    let scale = calculate_scale_factor(config, window);
+    let center = Vec2::new(window.width() as f32, window.height() as f32) * 0.5;
+    let offset = position - center;
+    Vec2::new(
+        camera_transform.translation.x + offset.x * scale.x,
+        camera_transform.translation.y + offset.y * scale.y,
+    )
+}
+
 fn calculate_scale_factor(config: &VirtualScreen, window: &Window) -> Vec3 {
     let x_scale = config.width / window.width() as f32;
     let y_scale = config.height / window.height() as f32;
@@ -0,0 +1,185 @@
+use crate::state::AppState;
+use simple_pong_bevy::ai::AiDifficulty;
+use simple_pong_bevy::net::INTERPOLATION_DELAY;
+use simple_pong_bevy::physics::SpeedUp;
+use simple_pong_bevy::replay::Replay;
+use simple_pong_bevy::rules::{parse_setting, MatchMode, MatchRules};
+use simple_pong_bevy::simulation::MAX_PLAYERS;
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+
+/// Who moves a paddle.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Controller {
+    Keyboard,
+    Computer,
+    /// The paddle follows the mouse cursor or a touch point.
+    Pointer,
+}
+
+/// Which end of an online match this game is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetworkRole {
+    /// Waits on the port for the other player and plays the left paddle.
+    Host(u16),
+    /// Connects to a host and plays the right paddle.
+    Join(SocketAddr),
+    /// Plays on a dedicated server, which picks the paddle.
+    Server(SocketAddr),
+    /// Watches the matches on a dedicated server.
+    Spectate(SocketAddr),
+    /// Watches a match another game broadcasts.
+    Watch(SocketAddr),
+}
+
+/// Options passed on the command line, e.g. `simple-pong-bevy --ai-right --ai-difficulty=hard`.
+#[derive(Debug)]
+pub struct LaunchOptions {
+    /// Indexed by player, see `MatchMode::layout`.
+    pub controllers: [Controller; MAX_PLAYERS],
+    pub difficulty: AiDifficulty,
+    pub rules: MatchRules,
+    pub speed_up: SpeedUp,
+    /// Save a replay of every finished match.
+    pub record: bool,
+    /// Watch this replay instead of playing.
+    pub replay: Option<Replay>,
+    /// Play online against another peer.
+    pub network: Option<NetworkRole>,
+    /// Stream the matches played here to spectators connecting to this port.
+    pub broadcast: Option<u16>,
+    /// How many seconds a spectator's view trails the match it watches.
+    pub spectator_delay: f32,
+    /// Shown to the others in a lobby.
+    pub name: String,
+    /// One of the themes in `assets/audio/themes.ini`, `None` plays the first one.
+    pub sound_theme: Option<String>,
+}
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        LaunchOptions {
+            controllers: [Controller::Keyboard; MAX_PLAYERS],
+            difficulty: AiDifficulty::Normal,
+            rules: MatchRules::default(),
+            speed_up: SpeedUp::default(),
+            record: false,
+            replay: None,
+            network: None,
+            broadcast: None,
+            spectator_delay: INTERPOLATION_DELAY,
+            name: env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_else(|_| "PLAYER".to_string()),
+            sound_theme: None,
+        }
+    }
+}
+impl LaunchOptions {
+    /// Asking for a computer player, a doubles or four-player match or a replay on the command
+    /// line skips the main menu, an online match starts by connecting to the other peer.
+    pub fn initial_state(&self) -> AppState {
+        if self.network.is_some() {
+            AppState::Connecting
+        } else if self.controllers.contains(&Controller::Computer)
+            || self.rules.mode != MatchMode::Singles
+            || self.replay.is_some()
+        {
+            AppState::Countdown
+        } else {
+            AppState::MainMenu
+        }
+    }
+
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> LaunchOptions {
+        let mut options = LaunchOptions::default();
+        for arg in args {
+            let (name, value) = match arg.find('=') {
+                Some(separator) => (&arg[..separator], Some(&arg[separator + 1..])),
+                None => (arg.as_str(), None),
+            };
+            if let Some(result) =
+                parse_setting(&mut options.rules, &mut options.speed_up, name, value)
+            {
+                if let Err(error) = result {
+                    eprintln!("{}", error);
+                }
+                continue;
+            }
+            match (name, value) {
+                ("--ai-left", None) => options.controllers[0] = Controller::Computer,
+                ("--ai-right", None) => options.controllers[1] = Controller::Computer,
+                // The third and fourth player are at the top and bottom in a four-player match
+                // and in the front lanes in doubles.
+                ("--ai-top", None) | ("--ai-left-front", None) => {
+                    options.controllers[2] = Controller::Computer
+                }
+                ("--ai-bottom", None) | ("--ai-right-front", None) => {
+                    options.controllers[3] = Controller::Computer
+                }
+                ("--mouse-left", None) => options.controllers[0] = Controller::Pointer,
+                ("--mouse-right", None) => options.controllers[1] = Controller::Pointer,
+                ("--ai-difficulty", Some(value)) => match AiDifficulty::from_name(value) {
+                    Some(difficulty) => options.difficulty = difficulty,
+                    None => eprintln!("Unknown AI difficulty {}", value),
+                },
+                ("--doubles", None) => options.rules.mode = MatchMode::Doubles,
+                ("--four-players", None) => options.rules.mode = MatchMode::FourPlayers,
+                ("--lives", Some(value)) => match value.parse() {
+                    Ok(lives) if lives > 0 => options.rules.lives = lives,
+                    _ => eprintln!("Invalid number of lives {}", value),
+                },
+                ("--record", None) => options.record = true,
+                ("--replay", Some(path)) => match Replay::load(Path::new(path)) {
+                    Ok(replay) => options.replay = Some(replay),
+                    Err(error) => eprintln!("Could not load replay {}: {}", path, error),
+                },
+                ("--host", Some(value)) => match value.parse() {
+                    Ok(port) => options.network = Some(NetworkRole::Host(port)),
+                    _ => eprintln!("Invalid port {}", value),
+                },
+                ("--join", Some(value)) => {
+                    options.network = parse_address(value).map(NetworkRole::Join)
+                }
+                ("--server", Some(value)) => {
+                    options.network = parse_address(value).map(NetworkRole::Server)
+                }
+                ("--spectate", Some(value)) => {
+                    options.network = parse_address(value).map(NetworkRole::Spectate)
+                }
+                ("--watch", Some(value)) => {
+                    options.network = parse_address(value).map(NetworkRole::Watch)
+                }
+                ("--broadcast", Some(value)) => match value.parse() {
+                    Ok(port) => options.broadcast = Some(port),
+                    _ => eprintln!("Invalid port {}", value),
+                },
+                ("--name", Some(value)) if !value.trim().is_empty() => {
+                    options.name = value.trim().to_string()
+                }
+                ("--sound-theme", Some(value)) => options.sound_theme = Some(value.to_string()),
+                ("--spectator-delay", Some(value)) => match value.parse::<f32>() {
+                    Ok(seconds) if seconds >= 0.0 => options.spectator_delay = seconds,
+                    _ => eprintln!("Invalid spectator delay {}, expected seconds", value),
+                },
+                _ => eprintln!("Ignoring unknown argument {}", arg),
+            }
+        }
+        if options.network.is_some() && options.rules.mode != MatchMode::Singles {
+            eprintln!("Online matches are for two players, ignoring --doubles and --four-players");
+            options.rules.mode = MatchMode::Singles;
+        }
+        options
+    }
+}
+
+fn parse_address(value: &str) -> Option<SocketAddr> {
+    let address = value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next());
+    if address.is_none() {
+        eprintln!("Invalid address {}, expected HOST:PORT", value);
+    }
+    address
+}
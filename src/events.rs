@@ -0,0 +1,139 @@
+use crate::simulation::{Side, SimEvent};
+
+/// A ball bounced off the paddle of `side`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BallHitPaddle {
+    pub side: Side,
+    /// Where the ball touched the paddle, in arena coordinates.
+    pub position: [f32; 2],
+    /// Arena units per second after the hit.
+    pub speed: f32,
+}
+
+/// A ball bounced off a wall.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BallHitWall {
+    pub position: [f32; 2],
+    pub speed: f32,
+}
+
+/// A ball went into the goal of `conceded`. In a four-player match that side lost a life,
+/// otherwise the other team scored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PointScored {
+    pub conceded: Side,
+}
+
+/// The match is over. It follows the simulation's winner rather than a `SimEvent`, online a
+/// predicted win can still be rolled back.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MatchWon {
+    pub winner: Side,
+}
+
+/// What happened in a match, in the terms the game's presentation reacts to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    BallHitPaddle(BallHitPaddle),
+    BallHitWall(BallHitWall),
+    PointScored(PointScored),
+}
+impl GameEvent {
+    /// The game events behind `events`. Eliminations are left out, the lost life already got
+    /// its `PointScored`, and so is the end of the match, see `MatchWon`.
+    pub fn from_sim_events(events: &[SimEvent]) -> Vec<GameEvent> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                SimEvent::PaddleHit(side, bounce) => {
+                    Some(GameEvent::BallHitPaddle(BallHitPaddle {
+                        side,
+                        position: bounce.position,
+                        speed: bounce.speed,
+                    }))
+                }
+                SimEvent::WallHit(bounce) => Some(GameEvent::BallHitWall(BallHitWall {
+                    position: bounce.position,
+                    speed: bounce.speed,
+                })),
+                SimEvent::Scored(scorer) => Some(GameEvent::PointScored(PointScored {
+                    conceded: match scorer {
+                        Side::Left => Side::Right,
+                        Side::Right => Side::Left,
+                        Side::Top => Side::Bottom,
+                        Side::Bottom => Side::Top,
+                    },
+                })),
+                SimEvent::LostLife(side) => {
+                    Some(GameEvent::PointScored(PointScored { conceded: side }))
+                }
+                SimEvent::Eliminated(_) | SimEvent::MatchOver(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AiController, AiDifficulty, AiPresets};
+    use crate::physics::SpeedUp;
+    use crate::rules::MatchRules;
+    use crate::simulation::{Simulation, ARENA_HEIGHT, ARENA_WIDTH, BALL_RADIUS};
+    use crate::utils::Rng;
+
+    #[test]
+    fn a_match_reports_hits_and_points() {
+        let presets = AiPresets::default();
+        let mut left = AiController::new(presets.get(AiDifficulty::Hard).clone(), Rng::new(3));
+        let mut right = AiController::new(presets.get(AiDifficulty::Easy).clone(), Rng::new(4));
+        let rules = MatchRules {
+            points_to_win: 3,
+            ..MatchRules::default()
+        };
+        let mut simulation = Simulation::new(rules, SpeedUp::default());
+        simulation.serve();
+
+        let mut stream = Vec::new();
+        while simulation.winner.is_none() && simulation.tick < 36_000 {
+            let inputs = [left.input(&simulation, 0), right.input(&simulation, 1)];
+            let events = simulation.step(&inputs);
+            stream.extend(GameEvent::from_sim_events(&events));
+        }
+
+        // The winning point is the last thing that happened.
+        let winner = simulation.winner.expect("the match never ended");
+        match stream.last() {
+            Some(GameEvent::PointScored(point)) => assert_ne!(point.conceded, winner),
+            event => panic!("expected the winning point, got {:?}", event),
+        }
+        let points = stream
+            .iter()
+            .filter(|event| matches!(event, GameEvent::PointScored(_)))
+            .count();
+        assert_eq!(
+            points as i32,
+            simulation.score_left + simulation.score_right
+        );
+
+        let mut hits = [0; 2];
+        for event in &stream {
+            match event {
+                GameEvent::BallHitPaddle(hit) => {
+                    hits[hit.side.index()] += 1;
+                    let near_left = hit.position[0] < ARENA_WIDTH / 2.0;
+                    assert_eq!(near_left, hit.side == Side::Left, "{:?}", hit);
+                    assert!(hit.speed > 0.0);
+                }
+                GameEvent::BallHitWall(hit) => {
+                    let bottom = (hit.position[1] - BALL_RADIUS).abs() < 1e-3;
+                    let top = (hit.position[1] - (ARENA_HEIGHT - BALL_RADIUS)).abs() < 1e-3;
+                    assert!(bottom || top, "{:?}", hit);
+                    assert!(hit.speed > 0.0);
+                }
+                _ => {}
+            }
+        }
+        assert!(hits[0] > 0 && hits[1] > 0, "{:?}", hits);
+    }
+}
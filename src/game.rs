@@ -1,9 +1,10 @@
 use crate::audio::initialise_audio;
-use crate::utils::{ScaleType, VirtualScreen};
+use crate::camera::{ScaleType, VirtualScreen};
+use crate::cli::{Controller, LaunchOptions};
+use crate::state::{AppState, GameState, StateScoped};
 use bevy::asset::{AssetServer, Assets, Handle};
-use bevy::core::{Time, Timer};
 use bevy::ecs::{Commands, Query, Res, ResMut};
-use bevy::math::Size;
+use bevy::math::{Rect, Size};
 use bevy::prelude::{
     BuildChildren, Camera2dBundle, CameraUiBundle, Entity, HorizontalAlign, NodeBundle, TextBundle,
     Transform, Vec2, Vec3, VerticalAlign,
@@ -11,73 +12,164 @@ use bevy::prelude::{
 use bevy::render::color::Color;
 use bevy::sprite::entity::SpriteSheetBundle;
 use bevy::sprite::{ColorMaterial, TextureAtlas, TextureAtlasSprite};
-use bevy::text::{TextAlignment, TextStyle};
+use bevy::text::{Font, TextAlignment, TextStyle};
 use bevy::ui::widget::Text;
 use bevy::ui::{AlignItems, JustifyContent, Style, Val};
+use simple_pong_bevy::ai::AiDifficulty;
+use simple_pong_bevy::net::{
+    Broadcaster, Handshake, RollbackSession, ServerConnection, Transport, UdpTransport,
+};
+use simple_pong_bevy::replay::Replay;
+use simple_pong_bevy::rules::MatchMode;
+use simple_pong_bevy::simulation::{
+    PaddleInput, Side, SimPaddle, Simulation, ARENA_HEIGHT, ARENA_WIDTH, MAX_PLAYERS,
+};
 
-pub const ARENA_HEIGHT: f32 = 100.0;
-pub const ARENA_WIDTH: f32 = 100.0;
-
-pub const PADDLE_HEIGHT: f32 = 16.0;
-pub const PADDLE_WIDTH: f32 = 4.0;
-
-pub const BALL_VELOCITY_X: f32 = 75.0;
-pub const BALL_VELOCITY_Y: f32 = 50.0;
-pub const BALL_RADIUS: f32 = 2.0;
-
-#[derive(PartialEq, Eq)]
-pub enum Side {
-    Left,
-    Right,
-}
-
+/// Renders the paddle of this player, the index into `Simulation::paddles`.
 pub struct Paddle {
+    pub player: usize,
     pub side: Side,
+    pub lane: usize,
     pub width: f32,
     pub height: f32,
 }
 impl Paddle {
-    fn new(side: Side) -> Paddle {
+    fn new(player: usize, paddle: &SimPaddle) -> Paddle {
         Paddle {
-            side,
-            width: PADDLE_WIDTH,
-            height: PADDLE_HEIGHT,
+            player,
+            side: paddle.side,
+            lane: paddle.lane,
+            width: paddle.width,
+            height: paddle.height,
         }
     }
+
+    /// True if this still renders `paddle`, the layout changes with the match mode.
+    pub fn shows(&self, paddle: &SimPaddle) -> bool {
+        self.side == paddle.side && self.lane == paddle.lane
+    }
 }
 
+/// Renders the ball at this index of `Simulation::balls`.
 pub struct Ball {
-    pub velocity: [f32; 2],
-    pub radius: f32,
+    pub index: usize,
 }
 
-pub struct ScoreBoard {
-    pub score_left: i32,
-    pub score_right: i32,
-}
+/// What the players do with their paddles this frame, indexed by player.
+#[derive(Default)]
+pub struct PaddleInputs(pub [PaddleInput; MAX_PLAYERS]);
+
+/// Arena height the mouse or touch controlled paddles follow, indexed by player. `None` for
+/// other paddles or while there is no pointer.
+#[derive(Default)]
+pub struct PointerTargets(pub [Option<f32>; 2]);
 
 pub struct ScoreText {
     pub p1_score: Entity,
     pub p2_score: Entity,
+    /// Lives of the four-player match, next to each side and indexed by `Side::index`.
+    pub lives: [Entity; MAX_PLAYERS],
+    /// Time left, match point and sudden death notices.
+    pub status: Entity,
+}
+impl ScoreText {
+    /// Shows the team scores of singles and doubles or the lives of a four-player match.
+    pub fn show(&self, simulation: &Simulation, text_query: &mut Query<&mut Text>) {
+        let four_players = simulation.rules.mode == MatchMode::FourPlayers;
+        let mut values = vec![
+            (self.p1_score, simulation.score_left.to_string()),
+            (self.p2_score, simulation.score_right.to_string()),
+        ];
+        if four_players {
+            values.iter_mut().for_each(|(_, value)| value.clear());
+        }
+        for side in Side::ALL.iter() {
+            let value = match simulation.lives.get(side.index()) {
+                Some(lives) if four_players && *lives > 0 => lives.to_string(),
+                Some(_) if four_players => "X".to_string(),
+                _ => String::new(),
+            };
+            values.push((self.lives[side.index()], value));
+        }
+
+        for (entity, value) in values {
+            if let Ok(mut text) = text_query.get_mut(entity) {
+                if text.value != value {
+                    text.value = value;
+                }
+            }
+        }
+    }
 }
 
 pub struct SpriteSheet {
     pub handle: Handle<TextureAtlas>,
 }
 
-pub struct StartTimer {
-    pub timer: Timer,
+pub struct UiAssets {
+    pub font: Handle<Font>,
+    pub transparent: Handle<ColorMaterial>,
+    /// Colors of the replay viewer's timeline.
+    pub timeline: Handle<ColorMaterial>,
+    pub timeline_progress: Handle<ColorMaterial>,
+    pub timeline_marker: Handle<ColorMaterial>,
+}
+
+/// Who plays the next match, chosen in the main menu.
+pub struct MatchSetup {
+    /// Indexed by player, see `MatchMode::layout`.
+    pub controllers: [Controller; MAX_PLAYERS],
+    pub difficulty: AiDifficulty,
+}
+
+/// The replay of the current match. Recorded while playing, or fed into the simulation instead
+/// of the players' inputs when `playback` is set.
+pub struct MatchReplay {
+    pub replay: Replay,
+    pub playback: bool,
+    /// Write the recording to the replays folder once the match is over.
+    pub save: bool,
+}
+
+/// The online match, if this is one. Against another peer, connecting goes through the
+/// handshake and the session then runs the match. On a dedicated server, or when watching
+/// another game's broadcast, the server runs it.
+#[derive(Default)]
+pub struct NetworkMatch {
+    pub handshake: Option<Handshake<UdpTransport>>,
+    pub session: Option<RollbackSession<UdpTransport>>,
+    pub server: Option<ServerConnection<Box<dyn Transport>>>,
+    /// Streams the matches played here to spectators, kept for the whole run of the game.
+    pub broadcaster: Option<Broadcaster>,
 }
-impl StartTimer {
-    fn new(seconds_to_start: f32) -> StartTimer {
-        StartTimer {
-            timer: Timer::from_seconds(seconds_to_start, false),
+impl NetworkMatch {
+    pub fn new(options: &LaunchOptions) -> NetworkMatch {
+        let broadcaster = options
+            .broadcast
+            .and_then(|port| match Broadcaster::bind(port) {
+                Ok(broadcaster) => {
+                    eprintln!("Broadcasting to spectators on port {}", port);
+                    Some(broadcaster)
+                }
+                Err(error) => {
+                    eprintln!("Could not broadcast on port {}: {}", port, error);
+                    None
+                }
+            });
+        NetworkMatch {
+            broadcaster,
+            ..Default::default()
         }
     }
+
+    pub fn is_online(&self) -> bool {
+        self.session.is_some() || self.server.is_some()
+    }
 }
 
 pub fn init_game(
     commands: &mut Commands,
+    options: Res<LaunchOptions>,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -87,31 +179,44 @@ pub fn init_game(
         handle: texture_atlas_handle.clone(),
     });
 
-    commands.insert_resource(ScoreBoard {
-        score_left: 0,
-        score_right: 0,
+    commands.insert_resource(MatchSetup {
+        controllers: options.controllers,
+        difficulty: options.difficulty,
+    });
+
+    commands.insert_resource(match &options.replay {
+        Some(replay) => MatchReplay {
+            replay: replay.clone(),
+            playback: true,
+            save: false,
+        },
+        None => MatchReplay {
+            replay: Replay::new(0, options.rules.clone(), options.speed_up.clone()),
+            playback: false,
+            save: options.record,
+        },
     });
 
-    commands.spawn((StartTimer::new(1.0),));
+    let ui_assets = UiAssets {
+        font: asset_server.load("font/square.ttf"),
+        transparent: materials.add(Color::NONE.into()),
+        timeline: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+        timeline_progress: materials.add(Color::rgb(0.8, 0.8, 0.8).into()),
+        timeline_marker: materials.add(Color::rgb(1.0, 0.8, 0.0).into()),
+    };
 
     initialise_camera(commands);
-    initialise_paddles(commands, &texture_atlas_handle);
 
-    initialise_scoreboard(commands, &asset_server, &mut materials);
-    initialise_audio(commands, &asset_server);
+    initialise_scoreboard(commands, &ui_assets);
+    initialise_audio(commands, options.sound_theme.as_deref());
+
+    commands.insert_resource(ui_assets);
 }
 
-pub fn start_game_system(
-    commands: &mut Commands,
-    time: Res<Time>,
-    sprite_sheet: Res<SpriteSheet>,
-    mut query: Query<(Entity, &mut StartTimer)>,
-) {
-    for (entity, mut timer) in query.iter_mut() {
-        if timer.timer.tick(time.delta_seconds()).just_finished() {
-            commands.despawn(entity);
-            initialise_ball(commands, &sprite_sheet.handle);
-        }
+/// Serves a new ball once the match starts, a paused match keeps its ball.
+pub fn spawn_ball_system(state: Res<GameState>, mut simulation: ResMut<Simulation>) {
+    if state.just_entered(AppState::Playing) && simulation.balls.is_empty() {
+        simulation.serve();
     }
 }
 
@@ -152,36 +257,28 @@ fn initialise_camera(commands: &mut Commands) {
         .spawn(CameraUiBundle::default());
 }
 
-fn initialise_paddles(commands: &mut Commands, texture_atlas_handle: &Handle<TextureAtlas>) {
-    let mut left_transform = Transform::default();
-    let mut right_transform = Transform::default();
-
-    let y = ARENA_HEIGHT / 2.0;
-    left_transform.translation = Vec3::new(PADDLE_WIDTH * 0.5, y, 0.0);
-    right_transform.translation = Vec3::new(ARENA_WIDTH - PADDLE_WIDTH * 0.5, y, 0.0);
-
-    let sprite_render_left = SpriteSheetBundle {
-        texture_atlas: (*texture_atlas_handle).clone(),
-        ..Default::default()
-    };
-    let sprite_render_right = SpriteSheetBundle {
+/// Spawns the paddle of a player, the match mode's layout decides how many there are.
+pub fn initialise_paddle<'a>(
+    commands: &'a mut Commands,
+    texture_atlas_handle: &Handle<TextureAtlas>,
+    player: usize,
+    paddle: &SimPaddle,
+) -> &'a mut Commands {
+    // The paddles are placed by the interpolation, from the simulation.
+    let sprite_render = SpriteSheetBundle {
         texture_atlas: (*texture_atlas_handle).clone(),
         ..Default::default()
     };
-
-    // Create a left plank entity.
-    commands
-        .spawn(sprite_render_left)
-        .with(left_transform)
-        .with(Paddle::new(Side::Left));
-
     commands
-        .spawn(sprite_render_right)
-        .with(right_transform)
-        .with(Paddle::new(Side::Right));
+        .spawn(sprite_render)
+        .with(Paddle::new(player, paddle))
 }
 
-fn initialise_ball(commands: &mut Commands, texture_atlas_handle: &Handle<TextureAtlas>) {
+pub fn initialise_ball(
+    commands: &mut Commands,
+    texture_atlas_handle: &Handle<TextureAtlas>,
+    index: usize,
+) {
     // Create the translation.
     let mut local_transform = Transform::default();
     local_transform.translation = Vec3::new(ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0, 0.0);
@@ -199,18 +296,16 @@ fn initialise_ball(commands: &mut Commands, texture_atlas_handle: &Handle<Textur
     commands
         .spawn(sprite_render)
         .with(local_transform)
-        .with(Ball {
-            radius: BALL_RADIUS,
-            velocity: [BALL_VELOCITY_X, BALL_VELOCITY_Y],
-        });
+        .with(Ball { index })
+        .with(StateScoped(&[
+            AppState::Playing,
+            AppState::Paused,
+            AppState::ReplayViewer,
+        ]));
 }
 
-fn initialise_scoreboard(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
-    let font = asset_server.load("font/square.ttf");
+fn initialise_scoreboard(commands: &mut Commands, ui_assets: &UiAssets) {
+    let font = ui_assets.font.clone();
 
     let mut p1_score = None;
     let mut p2_score = None;
@@ -222,7 +317,7 @@ fn initialise_scoreboard(
                 align_items: AlignItems::FlexEnd,
                 ..Default::default()
             },
-            material: materials.add(Color::NONE.into()),
+            material: ui_assets.transparent.clone(),
             ..Default::default()
         })
         .with_children(|parent| {
@@ -275,8 +370,109 @@ fn initialise_scoreboard(
                 .current_entity();
         });
 
+    let mut status = None;
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            material: ui_assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            status = parent
+                .spawn(TextBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(400.0), Val::Px(25.0)),
+                        // Right below the scores.
+                        margin: Rect {
+                            top: Val::Px(55.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text {
+                        value: String::new(),
+                        font: font.clone(),
+                        style: TextStyle {
+                            font_size: 20.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                            alignment: TextAlignment {
+                                vertical: VerticalAlign::Center,
+                                horizontal: HorizontalAlign::Center,
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .current_entity();
+        });
+
+    // Each player's lives in a four-player match sit next to their side of the arena.
+    let mut lives = Vec::new();
+    for side in Side::ALL.iter() {
+        let (justify_content, align_items) = match side {
+            Side::Left => (JustifyContent::FlexStart, AlignItems::Center),
+            Side::Right => (JustifyContent::FlexEnd, AlignItems::Center),
+            Side::Top => (JustifyContent::Center, AlignItems::FlexEnd),
+            Side::Bottom => (JustifyContent::Center, AlignItems::FlexStart),
+        };
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content,
+                    align_items,
+                    ..Default::default()
+                },
+                material: ui_assets.transparent.clone(),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                lives.push(
+                    parent
+                        .spawn(TextBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(50.0), Val::Px(40.0)),
+                                margin: Rect {
+                                    left: Val::Px(10.0),
+                                    right: Val::Px(10.0),
+                                    top: Val::Px(10.0),
+                                    bottom: Val::Px(10.0),
+                                },
+                                ..Default::default()
+                            },
+                            text: Text {
+                                value: String::new(),
+                                font: font.clone(),
+                                style: TextStyle {
+                                    font_size: 30.0,
+                                    color: Color::rgb(1.0, 1.0, 1.0),
+                                    alignment: TextAlignment {
+                                        vertical: VerticalAlign::Center,
+                                        horizontal: HorizontalAlign::Center,
+                                    },
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .current_entity()
+                        .unwrap(),
+                );
+            });
+    }
+
     commands.insert_resource(ScoreText {
         p1_score: p1_score.unwrap(),
         p2_score: p2_score.unwrap(),
+        lives: [lives[0], lives[1], lives[2], lives[3]],
+        status: status.unwrap(),
     });
 }
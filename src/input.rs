@@ -0,0 +1,519 @@
+use bevy::input::gamepad::{
+    Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType,
+};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::{Axis, Input};
+use simple_pong_bevy::simulation::{Side, MAX_PLAYERS};
+use simple_pong_bevy::utils::{config_path, Ini};
+use std::fmt;
+use std::fs;
+use std::io;
+
+const CONTROLS_FILE: &str = "controls.ini";
+const DEFAULT_DEAD_ZONE: f32 = 0.15;
+
+/// Something a player can do with a key, bound per player in the `InputMap`. The top and
+/// bottom players move right with `MoveUp` and left with `MoveDown`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    Pause,
+    /// Skips the countdown before a match.
+    Serve,
+}
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Pause,
+        Action::Serve,
+    ];
+
+    /// Key of the action in `controls.ini`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Pause => "pause",
+            Action::Serve => "serve",
+        }
+    }
+
+    /// How the action is shown for the player on `side`.
+    pub fn label(self, side: Side) -> &'static str {
+        match self {
+            Action::MoveUp if side.is_horizontal() => "MOVE RIGHT",
+            Action::MoveDown if side.is_horizontal() => "MOVE LEFT",
+            Action::MoveUp => "MOVE UP",
+            Action::MoveDown => "MOVE DOWN",
+            Action::Pause => "PAUSE",
+            Action::Serve => "SERVE",
+        }
+    }
+
+    /// The gamepad button for this action, movement uses the left stick instead.
+    fn gamepad_button(self) -> Option<GamepadButtonType> {
+        match self {
+            Action::MoveUp | Action::MoveDown => None,
+            Action::Pause => Some(GamepadButtonType::Start),
+            Action::Serve => Some(GamepadButtonType::South),
+        }
+    }
+}
+
+/// Keys that can be bound, stored in `controls.ini` by their `KeyCode` name. `Escape` cancels
+/// a rebind and is only kept as the left player's default pause key.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::Tab,
+    KeyCode::Back,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Grave,
+];
+
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .chain(&[KeyCode::Escape])
+        .copied()
+        .find(|key| key_name(*key) == name)
+}
+
+/// How a key is shown on screen, e.g. `W`, `UP` or `1`.
+pub fn key_label(key: KeyCode) -> String {
+    let name = key_name(key);
+    name.trim_start_matches("Key").to_uppercase()
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Left => "left",
+        Side::Right => "right",
+        Side::Top => "top",
+        Side::Bottom => "bottom",
+    }
+}
+
+/// One key bound to several actions.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Conflict {
+    pub key: KeyCode,
+    pub bindings: Vec<(Side, Action)>,
+}
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|(side, action)| {
+                format!(
+                    "{} {}",
+                    side_name(*side).to_uppercase(),
+                    action.label(*side)
+                )
+            })
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{} IS BOUND TO {}",
+            key_label(self.key),
+            bindings.join(" AND ")
+        )
+    }
+}
+
+/// The keys of all players, indexed by `Side::index` and then by position in `Action::ALL`. The
+/// front lanes of doubles use the top and bottom keys, like the third and fourth player.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMap {
+    bindings: [[KeyCode; 4]; MAX_PLAYERS],
+    /// Stick deflections below this fraction are ignored, worn sticks rarely rest at zero.
+    pub dead_zone: f32,
+}
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            bindings: [
+                [KeyCode::W, KeyCode::S, KeyCode::Escape, KeyCode::Space],
+                [KeyCode::Up, KeyCode::Down, KeyCode::P, KeyCode::Return],
+                [KeyCode::L, KeyCode::J, KeyCode::O, KeyCode::K],
+                [
+                    KeyCode::Numpad6,
+                    KeyCode::Numpad4,
+                    KeyCode::Numpad8,
+                    KeyCode::Numpad5,
+                ],
+            ],
+            dead_zone: DEFAULT_DEAD_ZONE,
+        }
+    }
+}
+impl InputMap {
+    /// Loads the bindings from the config directory, missing or broken entries keep their
+    /// default key.
+    pub fn load() -> InputMap {
+        let path = config_path(CONTROLS_FILE);
+        let input_map = match fs::read_to_string(&path) {
+            Ok(source) => {
+                let (input_map, errors) = InputMap::parse(&source);
+                for error in errors {
+                    eprintln!("Ignoring a control in {}: {}", path.display(), error);
+                }
+                input_map
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => InputMap::default(),
+            Err(error) => {
+                eprintln!("Could not load controls from {}: {}", path.display(), error);
+                InputMap::default()
+            }
+        };
+
+        for conflict in input_map.conflicts() {
+            eprintln!("Conflicting controls in {}: {}", path.display(), conflict);
+        }
+        input_map
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config_path(CONTROLS_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_ini())
+    }
+
+    /// Reads every entry on its own. Returns the bindings with the defaults in place of broken
+    /// entries, and an error for each of those.
+    pub fn parse(source: &str) -> (InputMap, Vec<String>) {
+        let mut input_map = InputMap::default();
+        let ini = match Ini::parse(source) {
+            Ok(ini) => ini,
+            Err(error) => return (input_map, vec![error.to_string()]),
+        };
+        let mut errors = Vec::new();
+        for side in Side::ALL.iter() {
+            let section = match ini.section(side_name(*side)) {
+                Some(section) => section,
+                None => continue,
+            };
+            for action in Action::ALL.iter() {
+                if let Some(name) = section.get(action.name()) {
+                    match key_from_name(name) {
+                        Some(key) => input_map.bind(*side, *action, key),
+                        None => errors.push(format!(
+                            "[{}] `{}` has unknown key `{}`",
+                            section.name,
+                            action.name(),
+                            name
+                        )),
+                    }
+                }
+            }
+        }
+        if let Some(section) = ini.section("gamepad") {
+            if section.get("dead_zone").is_some() {
+                match section.parse::<f32>("dead_zone") {
+                    Ok(dead_zone) if (0.0..1.0).contains(&dead_zone) => {
+                        input_map.dead_zone = dead_zone
+                    }
+                    Ok(dead_zone) => errors.push(format!(
+                        "[gamepad] `dead_zone` must be below 1, got {}",
+                        dead_zone
+                    )),
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+        (input_map, errors)
+    }
+
+    pub fn to_ini(&self) -> String {
+        let mut ini = Ini::default();
+        for side in Side::ALL.iter() {
+            let section = ini.section_mut(side_name(*side));
+            for action in Action::ALL.iter() {
+                section.set(action.name(), key_name(self.key(*side, *action)));
+            }
+        }
+        ini.section_mut("gamepad")
+            .set("dead_zone", self.dead_zone.to_string());
+        ini.to_string()
+    }
+
+    pub fn key(&self, side: Side, action: Action) -> KeyCode {
+        self.bindings[side.index()][action as usize]
+    }
+
+    pub fn bind(&mut self, side: Side, action: Action, key: KeyCode) {
+        self.bindings[side.index()][action as usize] = key;
+    }
+
+    pub fn pressed(&self, side: Side, action: Action, keyboard_input: &Input<KeyCode>) -> bool {
+        keyboard_input.pressed(self.key(side, action))
+    }
+
+    /// True if any player just pressed their key for `action`.
+    pub fn just_pressed(&self, action: Action, keyboard_input: &Input<KeyCode>) -> bool {
+        Side::ALL
+            .iter()
+            .any(|side| keyboard_input.just_pressed(self.key(*side, action)))
+    }
+
+    /// `1.0` while only up is held, `-1.0` while only down is held.
+    pub fn movement(&self, side: Side, keyboard_input: &Input<KeyCode>) -> f32 {
+        let mut movement = 0.0;
+        if self.pressed(side, Action::MoveUp, keyboard_input) {
+            movement += 1.0;
+        }
+        if self.pressed(side, Action::MoveDown, keyboard_input) {
+            movement -= 1.0;
+        }
+        movement
+    }
+
+    /// Deflection of the gamepad's left stick from `-1.0` to `1.0` along the direction the
+    /// paddle on `side` moves, `0.0` inside the dead zone.
+    pub fn stick(&self, gamepad: Gamepad, side: Side, axes: &Axis<GamepadAxis>) -> f32 {
+        let axis_type = if side.is_horizontal() {
+            GamepadAxisType::LeftStickX
+        } else {
+            GamepadAxisType::LeftStickY
+        };
+        let value = axes.get(GamepadAxis(gamepad, axis_type)).unwrap_or(0.0);
+        apply_dead_zone(value, self.dead_zone)
+    }
+
+    /// Labels of the keys the left and right player use for `action`, e.g. `ESC / P`.
+    pub fn labels(&self, action: Action) -> String {
+        let left = self.key(Side::Left, action);
+        let right = self.key(Side::Right, action);
+        if left == right {
+            key_label(left)
+        } else {
+            format!("{} / {}", key_label(left), key_label(right))
+        }
+    }
+
+    /// Keys bound to more than one action, in the order they are first used.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        for side in Side::ALL.iter() {
+            for action in Action::ALL.iter() {
+                let key = self.key(*side, *action);
+                match conflicts.iter_mut().find(|conflict| conflict.key == key) {
+                    Some(conflict) => conflict.bindings.push((*side, *action)),
+                    None => conflicts.push(Conflict {
+                        key,
+                        bindings: vec![(*side, *action)],
+                    }),
+                }
+            }
+        }
+        conflicts.retain(|conflict| conflict.bindings.len() > 1);
+        conflicts
+    }
+}
+
+/// Zero inside the dead zone, the rest of the range is stretched so the output still starts at
+/// zero and reaches full speed at full deflection.
+pub fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    if value.abs() <= dead_zone {
+        0.0
+    } else {
+        value.signum() * ((value.abs() - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+/// The gamepad of each player, indexed by `Side::index`. Pads are handed to the players in the
+/// order they are plugged in.
+#[derive(Default)]
+pub struct GamepadAssignments(pub [Option<Gamepad>; MAX_PLAYERS]);
+impl GamepadAssignments {
+    pub fn get(&self, side: Side) -> Option<Gamepad> {
+        self.0[side.index()]
+    }
+
+    /// Gives the pad to the first player without one, returns who got it.
+    pub fn assign(&mut self, gamepad: Gamepad) -> Option<Side> {
+        if self.0.contains(&Some(gamepad)) {
+            return None;
+        }
+        let index = self.0.iter().position(Option::is_none)?;
+        self.0[index] = Some(gamepad);
+        Some(Side::ALL[index])
+    }
+
+    /// Takes the pad away from its player, returns who lost it.
+    pub fn unassign(&mut self, gamepad: Gamepad) -> Option<Side> {
+        let index = self.0.iter().position(|pad| *pad == Some(gamepad))?;
+        self.0[index] = None;
+        Some(Side::ALL[index])
+    }
+
+    /// True if a player just pressed the button for `action` on their pad.
+    pub fn just_pressed(&self, action: Action, buttons: &Input<GamepadButton>) -> bool {
+        let button_type = match action.gamepad_button() {
+            Some(button_type) => button_type,
+            None => return false,
+        };
+        self.0
+            .iter()
+            .flatten()
+            .any(|gamepad| buttons.just_pressed(GamepadButton(*gamepad, button_type)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_survive_a_save_and_load() {
+        let mut input_map = InputMap::default();
+        input_map.bind(Side::Left, Action::MoveUp, KeyCode::Key1);
+        input_map.bind(Side::Right, Action::Serve, KeyCode::Numpad0);
+        input_map.dead_zone = 0.3;
+
+        assert_eq!(
+            InputMap::parse(&input_map.to_ini()),
+            (input_map, Vec::new())
+        );
+    }
+
+    #[test]
+    fn broken_entries_keep_their_default() {
+        let source = "\
+[left]
+move_up = Joystick
+move_down = Key2
+
+[gamepad]
+";
+        let (input_map, errors) = InputMap::parse(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("Joystick"), "{}", errors[0]);
+        assert_eq!(input_map.key(Side::Left, Action::MoveUp), KeyCode::W);
+        assert_eq!(input_map.key(Side::Left, Action::MoveDown), KeyCode::Key2);
+        assert_eq!(input_map.dead_zone, DEFAULT_DEAD_ZONE);
+
+        let (input_map, errors) = InputMap::parse("[gamepad]\ndead_zone = 2\n");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(input_map.dead_zone, DEFAULT_DEAD_ZONE);
+    }
+
+    #[test]
+    fn dead_zone_keeps_the_full_range() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.0);
+        assert_eq!(apply_dead_zone(-0.2, 0.2), 0.0);
+        assert!((apply_dead_zone(0.6, 0.2) - 0.5).abs() < 1e-6);
+        assert_eq!(apply_dead_zone(-1.0, 0.2), -1.0);
+    }
+
+    #[test]
+    fn conflicts_name_every_binding_of_the_key() {
+        assert!(InputMap::default().conflicts().is_empty());
+
+        let mut input_map = InputMap::default();
+        input_map.bind(Side::Right, Action::MoveUp, KeyCode::W);
+        assert_eq!(
+            input_map.conflicts(),
+            vec![Conflict {
+                key: KeyCode::W,
+                bindings: vec![(Side::Left, Action::MoveUp), (Side::Right, Action::MoveUp)],
+            }]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! Everything that decides how a match of Simple Pong plays out, plus the bookkeeping behind the
+//! game's music, sound effects and volumes. Nothing in here touches Bevy, so matches can be
+//! simulated in tests or on a server without a window.
+
+pub mod ai;
+pub mod events;
+pub mod manifest;
+pub mod mixer;
+pub mod music;
+pub mod net;
+pub mod physics;
+pub mod replay;
+pub mod rules;
+pub mod simulation;
+pub mod sound_theme;
+pub mod utils;
@@ -1,14 +1,37 @@
 mod audio;
+mod camera;
+mod cli;
 mod game;
+mod input;
+mod state;
 mod systems;
-mod utils;
+mod ui;
 
-use crate::game::{init_game, start_game_system};
-use crate::systems::{bounce_system, move_balls_system, paddle_system, winner_system};
-use crate::utils::camera_virtual_screen_system;
+use crate::audio::{music_system, sound_effects_system};
+use crate::camera::camera_virtual_screen_system;
+use crate::cli::LaunchOptions;
+use crate::game::{init_game, spawn_ball_system, NetworkMatch, PaddleInputs, PointerTargets};
+use crate::input::{GamepadAssignments, InputMap};
+use crate::state::{state_transition_system, GameState};
+use crate::systems::{
+    broadcast_system, game_events_system, gamepad_connection_system, interpolate_balls_system,
+    interpolate_paddles_system, match_rules_system, network_system, paddle_system, pointer_system,
+    simulation_system, winner_system,
+};
+use crate::ui::{
+    connecting_system, countdown_system, game_over_system, lobby_system, main_menu_system,
+    music_menu_system, pause_system, replay_viewer_system, settings_system, volume_system,
+};
+use bevy::audio::AudioPlugin;
 use bevy::prelude::*;
+use simple_pong_bevy::ai::AiPresets;
+use simple_pong_bevy::events::{BallHitPaddle, BallHitWall, MatchWon, PointScored};
+use simple_pong_bevy::mixer::Mixer;
+use simple_pong_bevy::simulation::{SimEvent, Simulation, TickClock};
 
 fn main() {
+    let options = LaunchOptions::from_args(std::env::args().skip(1));
+
     App::build()
         .add_resource(WindowDescriptor {
             title: "Simple Pong".to_string(),
@@ -17,13 +40,53 @@ fn main() {
             ..Default::default()
         })
         .add_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .add_plugins(DefaultPlugins)
+        .add_resource(GameState::new(options.initial_state()))
+        .add_resource(Simulation::new(
+            options.rules.clone(),
+            options.speed_up.clone(),
+        ))
+        .add_resource(TickClock::default())
+        .add_resource(PaddleInputs::default())
+        .add_resource(PointerTargets::default())
+        .add_resource(NetworkMatch::new(&options))
+        .add_resource(InputMap::load())
+        .add_resource(GamepadAssignments::default())
+        .add_resource(options)
+        .add_resource(AiPresets::load())
+        .add_resource(Mixer::load())
+        .add_event::<SimEvent>()
+        .add_event::<BallHitPaddle>()
+        .add_event::<BallHitWall>()
+        .add_event::<PointScored>()
+        .add_event::<MatchWon>()
+        // Sounds are played by `AudioPlayer`, bevy's own output would stay unused.
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<AudioPlugin>())
         .add_startup_system(init_game)
-        .add_system(start_game_system)
+        .add_system_to_stage(stage::PRE_UPDATE, state_transition_system)
+        .add_system(main_menu_system)
+        .add_system(settings_system)
+        .add_system(music_menu_system)
+        .add_system(volume_system)
+        .add_system(lobby_system)
+        .add_system(connecting_system)
+        .add_system(countdown_system)
+        .add_system(pause_system)
+        .add_system(game_over_system)
+        .add_system(replay_viewer_system)
+        .add_system(spawn_ball_system)
         .add_system(camera_virtual_screen_system)
+        .add_system(gamepad_connection_system)
         .add_system(paddle_system)
-        .add_system(move_balls_system)
-        .add_system(bounce_system)
+        .add_system(pointer_system)
+        .add_system(network_system)
+        .add_system(simulation_system)
+        .add_system(game_events_system)
         .add_system(winner_system)
+        .add_system(sound_effects_system)
+        .add_system(match_rules_system)
+        .add_system(broadcast_system)
+        .add_system(music_system)
+        .add_system_to_stage(stage::POST_UPDATE, interpolate_paddles_system)
+        .add_system_to_stage(stage::POST_UPDATE, interpolate_balls_system)
         .run();
 }
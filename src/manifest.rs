@@ -0,0 +1,207 @@
+use crate::utils::{asset_path, Ini, IniSection};
+use std::fs;
+
+const MANIFEST_FILE: &str = "audio/manifest.ini";
+
+/// A sound effect the game refers to by `name`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EffectEntry {
+    pub name: String,
+    /// Relative to the assets folder.
+    pub file: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrackEntry {
+    pub name: String,
+    pub file: String,
+    pub title: String,
+    pub artist: String,
+    pub license: String,
+}
+
+/// The sound files of the game, see `assets/audio/manifest.ini`.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct SoundManifest {
+    pub effects: Vec<EffectEntry>,
+    /// In playlist order.
+    pub tracks: Vec<TrackEntry>,
+}
+impl SoundManifest {
+    /// Loads the manifest from the assets folder. Without one the game stays silent, broken
+    /// entries are reported and left out.
+    pub fn load() -> SoundManifest {
+        let path = asset_path(MANIFEST_FILE);
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!(
+                    "Could not load the sound manifest {}, playing no sounds: {}",
+                    path.display(),
+                    error
+                );
+                return SoundManifest::default();
+            }
+        };
+        let (manifest, errors) = SoundManifest::parse(&source);
+        for error in errors {
+            eprintln!("Skipping a sound of {}: {}", path.display(), error);
+        }
+        manifest
+    }
+
+    /// Reads every section on its own. Returns the entries that are fine and an error for each
+    /// one that is not, naming its section.
+    pub fn parse(source: &str) -> (SoundManifest, Vec<String>) {
+        // The lines of every section, with the line number of their first one. Anything before
+        // the first section goes on its own.
+        let mut chunks: Vec<(usize, String)> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            if chunks.is_empty() || line.trim_start().starts_with('[') {
+                chunks.push((index, String::new()));
+            }
+            let chunk = &mut chunks.last_mut().unwrap().1;
+            chunk.push_str(line);
+            chunk.push('\n');
+        }
+
+        let mut manifest = SoundManifest::default();
+        let mut errors = Vec::new();
+        let mut seen = Vec::new();
+        for (first_line, chunk) in &chunks {
+            let ini = match Ini::parse(chunk) {
+                Ok(ini) => ini,
+                Err(mut error) => {
+                    error.line += first_line;
+                    errors.push(match chunk.lines().next().map(str::trim) {
+                        Some(header) if header.starts_with('[') => format!("{} {}", header, error),
+                        _ => error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            for section in ini.sections() {
+                if seen.contains(&section.name) {
+                    errors.push(format!("[{}] is listed twice", section.name));
+                    continue;
+                }
+                seen.push(section.name.clone());
+                if let Err(error) = manifest.add(section) {
+                    errors.push(error);
+                }
+            }
+        }
+        (manifest, errors)
+    }
+
+    fn add(&mut self, section: &IniSection) -> Result<(), String> {
+        let (kind, name) = match section.name.find('.') {
+            Some(separator) => (&section.name[..separator], &section.name[separator + 1..]),
+            None => (section.name.as_str(), ""),
+        };
+        if name.is_empty() {
+            return Err(format!("[{}] needs a name, e.g. [{}.bounce]", kind, kind));
+        }
+
+        match kind {
+            "effect" => self.effects.push(EffectEntry {
+                name: name.to_string(),
+                file: section.parse("file")?,
+            }),
+            "music" => self.tracks.push(TrackEntry {
+                name: name.to_string(),
+                file: section.parse("file")?,
+                title: section.parse("title")?,
+                artist: section.parse("artist")?,
+                license: section.parse("license")?,
+            }),
+            _ => {
+                return Err(format!(
+                    "[{}] is neither an effect nor music, expected [effect.{}] or [music.{}]",
+                    section.name, name, name
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn effect(&self, name: &str) -> Option<&EffectEntry> {
+        self.effects.iter().find(|effect| effect.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shipped_manifest_lists_every_sound() {
+        let (manifest, errors) = SoundManifest::parse(include_str!("../assets/audio/manifest.ini"));
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(manifest.effect("score").unwrap().file, "audio/score.ogg");
+        assert!(manifest.effect("bounce").is_some());
+        assert_eq!(manifest.effect("wall").unwrap().file, "audio/wall.ogg");
+        assert_eq!(manifest.tracks.len(), 2);
+        assert_eq!(manifest.tracks[0].title, "Where's My Jetpack");
+        assert_eq!(manifest.tracks[1].artist, "Computer Music All-Stars");
+    }
+
+    #[test]
+    fn broken_entries_are_named() {
+        let (_, errors) = SoundManifest::parse("[music.intro]\nfile = intro.ogg\ntitle = Intro\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("[music.intro]"), "{}", errors[0]);
+        assert!(errors[0].contains("artist"), "{}", errors[0]);
+
+        let (_, errors) = SoundManifest::parse("[sfx.bounce]\nfile = bounce.ogg\n");
+        assert!(errors[0].contains("[sfx.bounce]"), "{:?}", errors);
+        let (_, errors) = SoundManifest::parse("[effect]\nfile = bounce.ogg\n");
+        assert!(errors[0].contains("[effect]"), "{:?}", errors);
+        let (manifest, errors) =
+            SoundManifest::parse("[effect.bounce]\nfile = a.ogg\n[effect.bounce]\nfile = b.ogg\n");
+        assert_eq!(manifest.effect("bounce").unwrap().file, "a.ogg");
+        assert!(errors[0].contains("[effect.bounce]"), "{:?}", errors);
+
+        let (_, errors) = SoundManifest::parse("file = bounce.ogg\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            SoundManifest::parse(""),
+            (SoundManifest::default(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn broken_entries_leave_the_others_alone() {
+        let source = "\
+[effect.score]
+file = audio/score.ogg
+
+[music.intro]
+file = audio/intro.ogg
+title = Intro
+
+[effect.bounce]
+file = audio/bounce.ogg
+volume: loud
+
+[effect.wall]
+file = audio/wall.ogg
+
+[music.jetpack]
+file = audio/jetpack.ogg
+title = Where's My Jetpack
+artist = Computer Music All-Stars
+license = CC BY
+";
+        let (manifest, errors) = SoundManifest::parse(source);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("[music.intro]"), "{}", errors[0]);
+        assert!(errors[1].contains("[effect.bounce]"), "{}", errors[1]);
+        assert!(errors[1].contains("line 10"), "{}", errors[1]);
+        assert!(manifest.effect("score").is_some());
+        assert_eq!(manifest.effect("bounce"), None);
+        assert!(manifest.effect("wall").is_some());
+        assert_eq!(manifest.tracks.len(), 1);
+        assert_eq!(manifest.tracks[0].name, "jetpack");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::utils::{config_path, Ini};
+use std::fs;
+use std::io;
+
+const AUDIO_FILE: &str = "audio.ini";
+
+/// A group of sounds that share a volume. Everything goes through the master bus.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bus {
+    Master,
+    Music,
+    Effects,
+}
+impl Bus {
+    pub const ALL: [Bus; 3] = [Bus::Master, Bus::Music, Bus::Effects];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::Master => "master",
+            Bus::Music => "music",
+            Bus::Effects => "effects",
+        }
+    }
+}
+
+/// Volumes between `0.0` and `1.0` and mute switches of every bus, indexed like `Bus::ALL`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mixer {
+    volumes: [f32; 3],
+    muted: [bool; 3],
+}
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            // The music stays in the background of the sound effects.
+            volumes: [1.0, 0.6, 1.0],
+            muted: [false; 3],
+        }
+    }
+}
+impl Mixer {
+    /// Loads the volumes from the config directory, a missing or broken file gives the
+    /// defaults.
+    pub fn load() -> Mixer {
+        let path = config_path(AUDIO_FILE);
+        match fs::read_to_string(&path) {
+            Ok(source) => Mixer::parse(&source).unwrap_or_else(|error| {
+                eprintln!("Could not load volumes from {}: {}", path.display(), error);
+                Mixer::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Mixer::default(),
+            Err(error) => {
+                eprintln!("Could not load volumes from {}: {}", path.display(), error);
+                Mixer::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config_path(AUDIO_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_ini())
+    }
+
+    pub fn parse(source: &str) -> Result<Mixer, String> {
+        let ini = Ini::parse(source).map_err(|error| error.to_string())?;
+        let mut mixer = Mixer::default();
+        for bus in Bus::ALL.iter() {
+            let section = match ini.section(bus.name()) {
+                Some(section) => section,
+                None => continue,
+            };
+            if section.get("volume").is_some() {
+                let volume: f32 = section.parse("volume")?;
+                if !(0.0..=1.0).contains(&volume) {
+                    return Err(format!(
+                        "[{}] `volume` must be between 0 and 1, got {}",
+                        section.name, volume
+                    ));
+                }
+                mixer.set_volume(*bus, volume);
+            }
+            if section.get("muted").is_some() {
+                mixer.set_muted(*bus, section.parse("muted")?);
+            }
+        }
+        Ok(mixer)
+    }
+
+    pub fn to_ini(&self) -> String {
+        let mut ini = Ini::default();
+        for bus in Bus::ALL.iter() {
+            let section = ini.section_mut(bus.name());
+            section.set("volume", self.volume(*bus).to_string());
+            section.set("muted", self.is_muted(*bus).to_string());
+        }
+        ini.to_string()
+    }
+
+    pub fn volume(&self, bus: Bus) -> f32 {
+        self.volumes[bus as usize]
+    }
+
+    pub fn set_volume(&mut self, bus: Bus, volume: f32) {
+        self.volumes[bus as usize] = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_muted(&self, bus: Bus) -> bool {
+        self.muted[bus as usize]
+    }
+
+    pub fn set_muted(&mut self, bus: Bus, muted: bool) {
+        self.muted[bus as usize] = muted;
+    }
+
+    /// How loud a sound on `bus` plays, including the master bus.
+    pub fn gain(&self, bus: Bus) -> f32 {
+        let gain = |bus: Bus| {
+            if self.is_muted(bus) {
+                0.0
+            } else {
+                self.volume(bus)
+            }
+        };
+        match bus {
+            Bus::Master => gain(Bus::Master),
+            _ => gain(Bus::Master) * gain(bus),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volumes_survive_a_save_and_load() {
+        let mut mixer = Mixer::default();
+        mixer.set_volume(Bus::Master, 0.25);
+        mixer.set_volume(Bus::Effects, 0.0);
+        mixer.set_muted(Bus::Music, true);
+
+        assert_eq!(Mixer::parse(&mixer.to_ini()), Ok(mixer));
+        assert_eq!(Mixer::parse(""), Ok(Mixer::default()));
+
+        let error = Mixer::parse("[music]\nvolume = 2\n").unwrap_err();
+        assert!(error.contains("[music]"), "{}", error);
+    }
+
+    #[test]
+    fn buses_go_through_the_master_bus() {
+        let mut mixer = Mixer::default();
+        mixer.set_volume(Bus::Master, 0.5);
+        mixer.set_volume(Bus::Music, 0.5);
+        assert_eq!(mixer.gain(Bus::Music), 0.25);
+        assert_eq!(mixer.gain(Bus::Effects), 0.5);
+
+        mixer.set_muted(Bus::Master, true);
+        assert_eq!(mixer.gain(Bus::Effects), 0.0);
+        mixer.set_muted(Bus::Master, false);
+        mixer.set_muted(Bus::Effects, true);
+        assert_eq!(mixer.gain(Bus::Effects), 0.0);
+        assert_eq!(mixer.gain(Bus::Master), 0.5);
+    }
+}
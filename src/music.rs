@@ -0,0 +1,247 @@
+use crate::utils::{ByteReader, Rng};
+
+/// What happens once a track ends by itself.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Repeat {
+    /// Stop after the last track.
+    Off,
+    /// Start over with the first track.
+    All,
+    /// Play the same track again.
+    One,
+}
+impl Repeat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Repeat::Off => "off",
+            Repeat::All => "all",
+            Repeat::One => "one",
+        }
+    }
+
+    /// The mode after this one when cycling through them in a menu.
+    pub fn next(self) -> Repeat {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+}
+
+/// The order the music tracks are played in, as indices into the list of tracks.
+#[derive(Clone, Debug)]
+pub struct Playlist {
+    order: Vec<usize>,
+    /// Index into `order`, `None` once the playlist ran out.
+    position: Option<usize>,
+    shuffle: bool,
+    pub repeat: Repeat,
+    rng: Rng,
+}
+impl Playlist {
+    /// All tracks in their listed order, starting over after the last one.
+    pub fn new(tracks: usize, rng: Rng) -> Playlist {
+        Playlist {
+            order: (0..tracks).collect(),
+            position: if tracks > 0 { Some(0) } else { None },
+            shuffle: false,
+            repeat: Repeat::All,
+            rng,
+        }
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.position.map(|position| self.order[position])
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Shuffling keeps the current track playing and puts the others in random order after it.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        if shuffle == self.shuffle {
+            return;
+        }
+        self.shuffle = shuffle;
+        let current = self.current();
+        self.order.sort_unstable();
+        if shuffle {
+            self.shuffle_order(current);
+        }
+        if let Some(track) = current {
+            self.position = self.order.iter().position(|index| *index == track);
+        }
+    }
+
+    /// The track to play after the current one ended by itself, `None` if the music is over.
+    pub fn advance(&mut self) -> Option<usize> {
+        if self.repeat == Repeat::One {
+            return self.current();
+        }
+        self.step()
+    }
+
+    /// Skips to the next track, even while repeating a single one.
+    pub fn skip(&mut self) -> Option<usize> {
+        let repeat = self.repeat;
+        if repeat == Repeat::One {
+            self.repeat = Repeat::All;
+        }
+        let track = self.step();
+        self.repeat = repeat;
+        track
+    }
+
+    /// Goes back one track, from the first to the last one. After the music is over this is the
+    /// last track that played.
+    pub fn back(&mut self) -> Option<usize> {
+        let len = self.order.len();
+        if len == 0 {
+            return None;
+        }
+        self.position = Some(match self.position {
+            Some(position) => (position + len - 1) % len,
+            None => len - 1,
+        });
+        self.current()
+    }
+
+    fn step(&mut self) -> Option<usize> {
+        let position = self.position? + 1;
+        if position < self.order.len() {
+            self.position = Some(position);
+        } else if self.repeat == Repeat::Off {
+            self.position = None;
+        } else {
+            // Every round gets a new order, without the last track coming right back.
+            if self.shuffle {
+                let last = self.current();
+                self.shuffle_order(None);
+                if self.order.len() > 1 && self.order.first().copied() == last {
+                    self.order.swap(0, 1);
+                }
+            }
+            self.position = Some(0);
+        }
+        self.current()
+    }
+
+    /// Fisher-Yates, `first` stays in front.
+    fn shuffle_order(&mut self, first: Option<usize>) {
+        let start = match first.and_then(|track| self.order.iter().position(|i| *i == track)) {
+            Some(index) => {
+                self.order.swap(0, index);
+                1
+            }
+            None => 0,
+        };
+        for index in (start + 1..self.order.len()).rev() {
+            let other = start + (self.rng.next_u64() % (index - start + 1) as u64) as usize;
+            self.order.swap(index, other);
+        }
+    }
+}
+
+/// Length in seconds of an Ogg Vorbis file, from the sample rate in its first page and the
+/// sample position of its last page. `None` if the bytes are not Ogg Vorbis.
+pub fn ogg_duration(bytes: &[u8]) -> Option<f32> {
+    const CAPTURE: &[u8] = b"OggS";
+
+    // The first page holds the identification header: packet type 1, "vorbis", the version,
+    // the channel count and then the sample rate.
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(4).ok()? != CAPTURE {
+        return None;
+    }
+    reader.take(22).ok()?;
+    let segments = reader.u8().ok()?;
+    reader.take(usize::from(segments)).ok()?;
+    if reader.take(7).ok()? != b"\x01vorbis" {
+        return None;
+    }
+    reader.take(5).ok()?;
+    let sample_rate = reader.u32().ok()?;
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let last_page = bytes
+        .windows(CAPTURE.len())
+        .rposition(|window| window == CAPTURE)?;
+    let mut reader = ByteReader::new(&bytes[last_page + 6..]);
+    let samples = reader.u64().ok()? as i64;
+    if samples < 0 {
+        return None;
+    }
+    Some(samples as f32 / sample_rate as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_round(playlist: &mut Playlist) -> Vec<usize> {
+        let mut played = vec![playlist.current().unwrap()];
+        for _ in 1..playlist.order.len() {
+            played.push(playlist.advance().unwrap());
+        }
+        played
+    }
+
+    #[test]
+    fn playlists_play_in_order_and_follow_the_repeat_mode() {
+        let mut playlist = Playlist::new(3, Rng::new(1));
+        assert_eq!(play_round(&mut playlist), vec![0, 1, 2]);
+        assert_eq!(playlist.advance(), Some(0));
+        assert_eq!(playlist.back(), Some(2));
+
+        playlist.repeat = Repeat::One;
+        assert_eq!(playlist.advance(), Some(2));
+        assert_eq!(playlist.skip(), Some(0));
+        assert_eq!(playlist.repeat, Repeat::One);
+
+        playlist.repeat = Repeat::Off;
+        assert_eq!(playlist.skip(), Some(1));
+        assert_eq!(playlist.advance(), Some(2));
+        assert_eq!(playlist.advance(), None);
+        assert_eq!(playlist.back(), Some(2));
+
+        assert_eq!(Playlist::new(0, Rng::new(1)).skip(), None);
+    }
+
+    #[test]
+    fn shuffled_rounds_play_every_track_once() {
+        let mut playlist = Playlist::new(8, Rng::new(7));
+        playlist.advance();
+        playlist.set_shuffle(true);
+        assert_eq!(playlist.current(), Some(1));
+
+        for _ in 0..20 {
+            let mut round = play_round(&mut playlist);
+            let last = *round.last().unwrap();
+            round.sort_unstable();
+            assert_eq!(round, (0..8).collect::<Vec<_>>());
+            assert_ne!(playlist.advance(), Some(last));
+        }
+
+        let current = playlist.current();
+        playlist.set_shuffle(false);
+        assert_eq!(playlist.current(), current);
+        assert_eq!(playlist.order, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ogg_durations_come_from_the_last_page() {
+        let duration = ogg_duration(include_bytes!("../assets/audio/score.ogg")).unwrap();
+        assert!((duration - 0.691).abs() < 0.001, "{}", duration);
+        let duration = ogg_duration(include_bytes!("../assets/audio/bounce.ogg")).unwrap();
+        assert!((duration - 0.25).abs() < 0.001, "{}", duration);
+        let duration = ogg_duration(include_bytes!("../assets/audio/wall.ogg")).unwrap();
+        assert!((duration - 0.15).abs() < 0.001, "{}", duration);
+
+        assert_eq!(ogg_duration(b"OggS"), None);
+        assert_eq!(ogg_duration(b"RIFF and not an ogg file at all"), None);
+    }
+}
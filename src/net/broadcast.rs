@@ -0,0 +1,148 @@
+use crate::net::{Message, TcpTransport, Transport};
+use crate::replay::Snapshot;
+use crate::simulation::{SimEvent, Simulation};
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+
+/// Streams a running match to spectators over TCP. They only listen, everything they send is
+/// dropped. Their games show it with `ServerConnection`, like a match on a dedicated server.
+pub struct Broadcaster {
+    listener: TcpListener,
+    spectators: Vec<TcpTransport>,
+    /// Keeps counting up when the match starts over, the spectators order the states by it.
+    broadcast_tick: u64,
+    last_tick: Option<u64>,
+}
+impl Broadcaster {
+    pub fn bind(port: u16) -> io::Result<Broadcaster> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcaster {
+            listener,
+            spectators: Vec::new(),
+            broadcast_tick: 0,
+            last_tick: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Call once per frame with what happened since the last call. Lets new spectators in and
+    /// sends everyone the current state, spectators that left or do not keep up are dropped.
+    pub fn update(&mut self, simulation: &Simulation, events: Vec<SimEvent>) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let mut spectator = TcpTransport::new(stream)?;
+                    let welcome = Message::Welcome {
+                        side: None,
+                        rules: simulation.rules.clone(),
+                        speed_up: simulation.speed_up.clone(),
+                    };
+                    if spectator.send(&welcome.to_bytes()).is_ok() {
+                        self.spectators.push(spectator);
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.broadcast_tick += match self.last_tick {
+            Some(last_tick) if simulation.tick >= last_tick => simulation.tick - last_tick,
+            _ => 1,
+        };
+        self.last_tick = Some(simulation.tick);
+        let state = Message::State {
+            server_tick: self.broadcast_tick,
+            snapshot: Snapshot::capture(simulation),
+            winner: simulation.winner,
+            events,
+        }
+        .to_bytes();
+
+        let mut index = 0;
+        while index < self.spectators.len() {
+            if forward(&mut self.spectators[index], &state).is_ok() {
+                index += 1;
+            } else {
+                self.spectators.swap_remove(index);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn forward(spectator: &mut TcpTransport, state: &[u8]) -> io::Result<()> {
+    // Spectators only ever knock, there is nothing to answer.
+    while spectator.receive()?.is_some() {}
+    spectator.send(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ServerConnection;
+    use crate::physics::SpeedUp;
+    use crate::rules::MatchRules;
+    use crate::simulation::{PaddleInput, TICK_DELTA};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn spectators_see_the_match_and_its_events() {
+        let mut broadcaster = Broadcaster::bind(0).unwrap();
+        let address = SocketAddr::from(([127, 0, 0, 1], broadcaster.local_addr().unwrap().port()));
+        let mut spectator =
+            ServerConnection::new(TcpTransport::connect(address).unwrap(), true, 0.0);
+
+        let rules = MatchRules {
+            points_to_win: 2,
+            ..MatchRules::default()
+        };
+        let mut simulation = Simulation::new(rules.clone(), SpeedUp::default());
+        simulation.serve();
+        let mut shown = Simulation::new(rules, SpeedUp::default());
+        // Both paddles leave the middle, every serve scores.
+        let inputs = [PaddleInput::from_axis(1.0), PaddleInput::from_axis(-1.0)];
+
+        let mut points = 0;
+        for _ in 0..10_000 {
+            let events = simulation.step(&inputs);
+            broadcaster.update(&simulation, events).unwrap();
+            spectator
+                .update(TICK_DELTA, PaddleInput::default())
+                .unwrap();
+            spectator.snapshots.show(1.0, &mut shown);
+            points += spectator
+                .snapshots
+                .take_events()
+                .iter()
+                .filter(|event| matches!(event, SimEvent::Scored(_)))
+                .count();
+            if shown.winner.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_micros(200));
+        }
+
+        assert!(spectator.is_welcomed());
+        assert_eq!(spectator.side(), None);
+        assert_eq!(broadcaster.spectators(), 1);
+        assert_eq!(shown.winner, simulation.winner);
+        assert_eq!(
+            (shown.score_left, shown.score_right),
+            (simulation.score_left, simulation.score_right)
+        );
+        assert_eq!(
+            points as i32,
+            simulation.score_left + simulation.score_right
+        );
+    }
+}
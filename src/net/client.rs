@@ -0,0 +1,297 @@
+use crate::net::{Message, Transport};
+use crate::physics::SpeedUp;
+use crate::replay::Snapshot;
+use crate::rules::MatchRules;
+use crate::simulation::{PaddleInput, Side, SimEvent, Simulation, TICK_DELTA};
+use std::collections::VecDeque;
+use std::io;
+
+/// Seconds the view stays behind the newest state by default. There is then usually a later
+/// state to move towards, even when a packet is late or lost.
+pub const INTERPOLATION_DELAY: f32 = 0.1;
+/// Views further off than this jump instead of catching up slowly.
+const MAX_VIEW_DRIFT_TICKS: f32 = 30.0;
+/// States kept beyond the delay, in case they are late.
+const EXTRA_BUFFERED_SNAPSHOTS: usize = 60;
+/// Seconds between the connect messages spectators send to stay on the server.
+const KEEP_ALIVE_SECONDS: f32 = 1.0;
+
+struct BufferedState {
+    server_tick: u64,
+    snapshot: Snapshot,
+    winner: Option<Side>,
+    /// Handed out once the view reaches this state.
+    events: Vec<SimEvent>,
+}
+
+/// The states a server sent, to show the match some time in the past and smoothly in between.
+/// What happened in a state only comes out once it is shown, so sounds and the score change
+/// together with the picture.
+pub struct SnapshotBuffer {
+    /// Ordered by server tick.
+    states: VecDeque<BufferedState>,
+    delay_ticks: f32,
+    /// Server tick being shown.
+    view_tick: Option<f32>,
+    events: Vec<SimEvent>,
+}
+impl Default for SnapshotBuffer {
+    fn default() -> Self {
+        SnapshotBuffer::new(INTERPOLATION_DELAY)
+    }
+}
+impl SnapshotBuffer {
+    /// Shows the states `delay` seconds after they were sent.
+    pub fn new(delay: f32) -> SnapshotBuffer {
+        SnapshotBuffer {
+            states: VecDeque::new(),
+            delay_ticks: delay.max(0.0) / TICK_DELTA,
+            view_tick: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        server_tick: u64,
+        snapshot: Snapshot,
+        winner: Option<Side>,
+        events: Vec<SimEvent>,
+    ) {
+        let index = self
+            .states
+            .iter()
+            .position(|state| state.server_tick >= server_tick)
+            .unwrap_or(self.states.len());
+        if self.states.get(index).map(|state| state.server_tick) == Some(server_tick) {
+            return;
+        }
+        // Too late to be shown, but what happened in it still counts.
+        if matches!(self.view_tick, Some(view) if (server_tick as f32) < view) {
+            self.events.extend(events);
+            return;
+        }
+        self.states.insert(
+            index,
+            BufferedState {
+                server_tick,
+                snapshot,
+                winner,
+                events,
+            },
+        );
+        if self.states.len() > self.delay_ticks as usize + EXTRA_BUFFERED_SNAPSHOTS {
+            let dropped = self.states.pop_front().unwrap();
+            self.events.extend(dropped.events);
+        }
+    }
+
+    /// Moves the view on by `ticks` and shows it in `simulation`, see `Snapshot::show_until`.
+    /// Returns how far the view is between the two snapshots, `None` before the first one.
+    pub fn show(&mut self, ticks: f32, simulation: &mut Simulation) -> Option<f32> {
+        let newest = self.states.back()?.server_tick as f32;
+        let target = newest - self.delay_ticks;
+        let view = match self.view_tick {
+            Some(view) if (view + ticks - target).abs() <= MAX_VIEW_DRIFT_TICKS => {
+                // Drift towards the target slowly, jumps would show.
+                view + ticks + (target - view - ticks) * 0.1
+            }
+            _ => target,
+        };
+        let view = view.min(newest).max(self.states[0].server_tick as f32);
+        self.view_tick = Some(view);
+
+        while self.states.len() > 1 && self.states[1].server_tick as f32 <= view {
+            self.states.pop_front();
+        }
+        for state in &mut self.states {
+            if state.server_tick as f32 > view {
+                break;
+            }
+            self.events.append(&mut state.events);
+        }
+
+        let from = &self.states[0];
+        let to = self.states.get(1).unwrap_or(from);
+        from.snapshot.show_until(&to.snapshot, simulation);
+        simulation.winner = from.winner;
+        Some(if to.server_tick > from.server_tick {
+            (view - from.server_tick as f32) / (to.server_tick - from.server_tick) as f32
+        } else {
+            0.0
+        })
+    }
+
+    /// What happened in the states shown since the last call.
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The winner in the newest state.
+    pub fn newest_winner(&self) -> Option<Side> {
+        self.states.back().and_then(|state| state.winner)
+    }
+}
+
+/// A game connected to a dedicated server as a player or a spectator, or watching another game
+/// that broadcasts its match. The other side runs the match, this game only sends its input and
+/// shows the states it gets back.
+pub struct ServerConnection<T> {
+    transport: T,
+    spectator: bool,
+    welcome: Option<(Option<Side>, MatchRules, SpeedUp)>,
+    pub snapshots: SnapshotBuffer,
+    input_sequence: u64,
+    since_sent: f32,
+    since_received: f32,
+}
+impl<T: Transport> ServerConnection<T> {
+    /// Shows the match `delay` seconds late, see `SnapshotBuffer::new`.
+    pub fn new(transport: T, spectator: bool, delay: f32) -> ServerConnection<T> {
+        ServerConnection {
+            transport,
+            spectator,
+            welcome: None,
+            snapshots: SnapshotBuffer::new(delay),
+            input_sequence: 0,
+            since_sent: KEEP_ALIVE_SECONDS,
+            since_received: 0.0,
+        }
+    }
+
+    pub fn is_welcomed(&self) -> bool {
+        self.welcome.is_some()
+    }
+
+    /// The side this game plays, `None` when watching.
+    pub fn side(&self) -> Option<Side> {
+        self.welcome.as_ref().and_then(|(side, _, _)| *side)
+    }
+
+    /// The rules and speed up of the server's matches, once welcomed.
+    pub fn settings(&self) -> Option<(&MatchRules, &SpeedUp)> {
+        self.welcome
+            .as_ref()
+            .map(|(_, rules, speed_up)| (rules, speed_up))
+    }
+
+    /// The winner of the server's match, as of the newest state.
+    pub fn winner(&self) -> Option<Side> {
+        self.snapshots.newest_winner()
+    }
+
+    /// Seconds since the last packet from the server.
+    pub fn silence(&self) -> f32 {
+        self.since_received
+    }
+
+    /// Call once per frame with the seconds that passed. Sends `input` for players, knocks on
+    /// the server until it answers, and buffers the states that arrived.
+    pub fn update(&mut self, seconds: f32, input: PaddleInput) -> io::Result<()> {
+        self.since_received += seconds;
+        self.since_sent += seconds;
+
+        while let Some(packet) = self.transport.receive()? {
+            match Message::from_bytes(&packet) {
+                Some(Message::Welcome {
+                    side,
+                    rules,
+                    speed_up,
+                }) => {
+                    self.since_received = 0.0;
+                    self.welcome = Some((side, rules, speed_up));
+                }
+                Some(Message::State {
+                    server_tick,
+                    snapshot,
+                    winner,
+                    events,
+                }) => {
+                    self.since_received = 0.0;
+                    self.snapshots.push(server_tick, snapshot, winner, events);
+                }
+                _ => {}
+            }
+        }
+
+        if self.welcome.is_none()
+            || (self.side().is_none() && self.since_sent >= KEEP_ALIVE_SECONDS)
+        {
+            self.since_sent = 0.0;
+            self.transport.send(
+                &Message::Connect {
+                    spectator: self.spectator,
+                }
+                .to_bytes(),
+            )?;
+        } else if self.side().is_some() {
+            self.input_sequence += 1;
+            self.since_sent = 0.0;
+            self.transport.send(
+                &Message::Input {
+                    sequence: self.input_sequence,
+                    input,
+                }
+                .to_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Tells the server right away instead of letting it time out.
+    pub fn disconnect(&mut self) -> io::Result<()> {
+        self.transport.send(&Message::Disconnect.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Bounce;
+
+    fn snapshot(tick: u64, ball_x: f32) -> Snapshot {
+        Snapshot {
+            tick,
+            paddles: vec![[2.0, 50.0], [98.0, 50.0]],
+            balls: vec![[ball_x, 50.0]],
+            score_left: 0,
+            score_right: 0,
+            lives: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn the_view_moves_between_snapshots() {
+        let mut buffer = SnapshotBuffer::new(0.1);
+        let delay_ticks = 0.1 / TICK_DELTA;
+        let mut simulation = Simulation::new(MatchRules::default(), SpeedUp::default());
+        assert_eq!(buffer.show(1.0, &mut simulation), None);
+
+        let bounce = SimEvent::WallHit(Bounce {
+            position: [20.0, 2.0],
+            speed: 60.0,
+        });
+        // Every other state got lost, and they arrive out of order.
+        for tick in (0..=20).rev().filter(|tick| tick % 2 == 0) {
+            let events = if tick == 20 { vec![bounce] } else { Vec::new() };
+            buffer.push(tick, snapshot(tick, tick as f32), None, events);
+        }
+        let alpha = buffer.show(1.0, &mut simulation).unwrap();
+        let ball = &simulation.balls[0];
+        let shown_x =
+            ball.previous_position[0] + (ball.position[0] - ball.previous_position[0]) * alpha;
+        assert!((shown_x - (20.0 - delay_ticks)).abs() < 0.01);
+        assert!(
+            buffer.take_events().is_empty(),
+            "events before they are shown"
+        );
+
+        buffer.push(22, snapshot(22, 22.0), None, Vec::new());
+        for _ in 0..10 {
+            buffer.show(1.0, &mut simulation).unwrap();
+        }
+        assert!(simulation.balls[0].previous_position[0] >= 14.0);
+        assert_eq!(buffer.take_events(), vec![bounce]);
+        assert!(buffer.take_events().is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::net::{Message, RollbackSession, Transport};
+use crate::physics::SpeedUp;
+use crate::rules::MatchRules;
+use crate::simulation::{Side, Simulation};
+use std::io;
+
+/// Agrees on the settings before a match starts. The joining peer, playing the right paddle,
+/// knocks until the hosting peer answers with its settings. The host plays the left paddle and
+/// repeats the settings until the joining peer confirms them.
+pub struct Handshake<T> {
+    transport: T,
+    side: Side,
+    settings: Option<(MatchRules, SpeedUp)>,
+    done: bool,
+}
+impl<T: Transport> Handshake<T> {
+    pub fn host(transport: T, rules: MatchRules, speed_up: SpeedUp) -> Handshake<T> {
+        Handshake {
+            transport,
+            side: Side::Left,
+            settings: Some((rules, speed_up)),
+            done: false,
+        }
+    }
+
+    pub fn join(transport: T) -> Handshake<T> {
+        Handshake {
+            transport,
+            side: Side::Right,
+            settings: None,
+            done: false,
+        }
+    }
+
+    /// Call every frame, `true` once both peers can start.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        while let Some(packet) = self.transport.receive()? {
+            match (Message::from_bytes(&packet), self.side) {
+                (Some(Message::Hello { rules, speed_up }), Side::Right) => {
+                    self.settings = Some((rules, speed_up));
+                    self.transport.send(&Message::Ready.to_bytes())?;
+                    self.done = true;
+                }
+                // Inputs mean the answer got lost but the other peer already started.
+                (Some(Message::Ready), Side::Left) | (Some(Message::Inputs { .. }), Side::Left) => {
+                    self.done = true
+                }
+                _ => {}
+            }
+        }
+        if !self.done {
+            let message = match (self.side, &self.settings) {
+                (Side::Left, Some((rules, speed_up))) => Message::Hello {
+                    rules: rules.clone(),
+                    speed_up: speed_up.clone(),
+                },
+                _ => Message::Join,
+            };
+            self.transport.send(&message.to_bytes())?;
+        }
+        Ok(self.done)
+    }
+
+    /// The served match both peers agreed on. Only call once `poll` returned `true`.
+    pub fn into_session(self) -> RollbackSession<T> {
+        let (rules, speed_up) = self
+            .settings
+            .expect("handshake is not done, the settings are unknown");
+        let mut simulation = Simulation::new(rules, speed_up);
+        simulation.serve();
+        RollbackSession::new(self.transport, self.side, simulation)
+    }
+}
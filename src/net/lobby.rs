@@ -0,0 +1,688 @@
+use crate::net::protocol::clip;
+use crate::net::{Handshake, Message, UdpTransport};
+use crate::physics::SpeedUp;
+use crate::rules::MatchRules;
+use crate::utils::Rng;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+/// Lobbies are hosted on the first free one of `LOBBY_PORTS` ports from here on, finders ask
+/// all of them.
+const LOBBY_PORT: u16 = 7780;
+const LOBBY_PORTS: u16 = 8;
+/// Longest player name and chat line, in bytes.
+const MAX_NAME_LENGTH: usize = 16;
+pub const MAX_CHAT_LENGTH: usize = 80;
+/// Seconds between the packets a host and its guest send each other.
+const SEND_INTERVAL: f32 = 0.1;
+/// Seconds between two searches for lobbies.
+const SEARCH_INTERVAL: f32 = 1.0;
+/// Seconds without a word before a lobby is no longer listed, or a host or guest is gone.
+const TIMEOUT: f32 = 3.0;
+/// The latest chat lines go along with every description of the lobby, so lost ones are sent
+/// again.
+const CHAT_LINES_PER_PACKET: usize = 4;
+const MAX_PACKET_SIZE: usize = 1500;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LobbyMember {
+    pub name: String,
+    pub ready: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChatLine {
+    pub name: String,
+    pub text: String,
+}
+
+/// A lobby found on the network.
+#[derive(Clone, Debug)]
+pub struct LobbyListing {
+    pub address: SocketAddr,
+    pub id: u64,
+    /// The host comes first.
+    pub members: Vec<LobbyMember>,
+    pub rules: MatchRules,
+    pub speed_up: SpeedUp,
+    silence: f32,
+}
+
+/// Looks for lobbies on the local network. Every lobby port is asked with a broadcast, and on
+/// this machine directly so local lobbies are found without a network too.
+pub struct LobbyFinder {
+    socket: UdpSocket,
+    lobbies: Vec<LobbyListing>,
+    since_search: Option<f32>,
+}
+impl LobbyFinder {
+    pub fn new() -> io::Result<LobbyFinder> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(LobbyFinder {
+            socket,
+            lobbies: Vec::new(),
+            since_search: None,
+        })
+    }
+
+    /// The lobbies that answered lately, in the order they were found.
+    pub fn lobbies(&self) -> &[LobbyListing] {
+        &self.lobbies
+    }
+
+    /// Call every frame.
+    pub fn update(&mut self, seconds: f32) -> io::Result<()> {
+        let mut since_search = self
+            .since_search
+            .map_or(SEARCH_INTERVAL, |since| since + seconds);
+        if since_search >= SEARCH_INTERVAL {
+            let discover = Message::Discover.to_bytes();
+            for port in LOBBY_PORT..LOBBY_PORT + LOBBY_PORTS {
+                for ip in &[Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                    // Without a network there is nobody to broadcast to, which is fine.
+                    let _ = self.socket.send_to(&discover, (*ip, port));
+                }
+            }
+            since_search = 0.0;
+        }
+        self.since_search = Some(since_search);
+
+        for lobby in &mut self.lobbies {
+            lobby.silence += seconds;
+        }
+        while let Some((packet, from)) = receive(&self.socket)? {
+            if let Some(Message::Lobby {
+                id,
+                members,
+                rules,
+                speed_up,
+                ..
+            }) = Message::from_bytes(&packet)
+            {
+                let listing = LobbyListing {
+                    address: from,
+                    id,
+                    members,
+                    rules,
+                    speed_up,
+                    silence: 0.0,
+                };
+                // Lobbies on this machine answer twice, and a lobby moves with its host.
+                match self.lobbies.iter_mut().find(|lobby| lobby.id == id) {
+                    Some(known) => *known = listing,
+                    None => self.lobbies.push(listing),
+                }
+            }
+        }
+        self.lobbies.retain(|lobby| lobby.silence < TIMEOUT);
+        Ok(())
+    }
+}
+
+struct Guest {
+    address: SocketAddr,
+    member: LobbyMember,
+    /// How many of the guest's chat lines are in the chat.
+    heard: u64,
+    silence: f32,
+}
+
+enum Role {
+    Host {
+        guest: Option<Guest>,
+    },
+    Guest {
+        host: SocketAddr,
+        /// The host answered, so this player is in the lobby.
+        entered: bool,
+        members: Vec<LobbyMember>,
+        silence: f32,
+        /// Own chat lines the host did not hear yet, and how many it heard.
+        unheard: VecDeque<String>,
+        heard: u64,
+        /// Number of the host's next chat line.
+        chat_received: u64,
+    },
+}
+
+/// Where two players on the local network meet before an online match. One of them hosts
+/// the lobby, the host's settings apply. Both can chat and get ready, and once both are the
+/// lobby turns into the match's handshake. When the host leaves, the guest takes the lobby
+/// over and others can enter it again.
+pub struct Lobby {
+    socket: UdpSocket,
+    id: u64,
+    name: String,
+    ready: bool,
+    role: Role,
+    rules: MatchRules,
+    speed_up: SpeedUp,
+    chat: Vec<ChatLine>,
+    since_sent: f32,
+    started: bool,
+}
+impl Lobby {
+    pub fn host(name: &str, rules: MatchRules, speed_up: SpeedUp) -> io::Result<Lobby> {
+        Ok(Lobby {
+            socket: bind_lobby_port()?,
+            id: Rng::from_time().next_u64(),
+            name: clip(name, MAX_NAME_LENGTH).to_string(),
+            ready: false,
+            role: Role::Host { guest: None },
+            rules,
+            speed_up,
+            chat: Vec::new(),
+            since_sent: SEND_INTERVAL,
+            started: false,
+        })
+    }
+
+    pub fn join(name: &str, listing: &LobbyListing) -> io::Result<Lobby> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_nonblocking(true)?;
+        Ok(Lobby {
+            socket,
+            id: listing.id,
+            name: clip(name, MAX_NAME_LENGTH).to_string(),
+            ready: false,
+            role: Role::Guest {
+                host: listing.address,
+                entered: false,
+                members: listing.members.clone(),
+                silence: 0.0,
+                unheard: VecDeque::new(),
+                heard: 0,
+                chat_received: 0,
+            },
+            rules: listing.rules.clone(),
+            speed_up: listing.speed_up.clone(),
+            chat: Vec::new(),
+            since_sent: SEND_INTERVAL,
+            started: false,
+        })
+    }
+
+    /// Stays the same when the host changes.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    pub fn rules(&self) -> &MatchRules {
+        &self.rules
+    }
+
+    pub fn speed_up(&self) -> &SpeedUp {
+        &self.speed_up
+    }
+
+    /// Everyone in the lobby, the host first.
+    pub fn members(&self) -> Vec<LobbyMember> {
+        match &self.role {
+            Role::Host { guest } => {
+                let mut members = vec![LobbyMember {
+                    name: self.name.clone(),
+                    ready: self.ready,
+                }];
+                members.extend(guest.iter().map(|guest| guest.member.clone()));
+                members
+            }
+            Role::Guest { members, .. } => members.clone(),
+        }
+    }
+
+    pub fn chat(&self) -> &[ChatLine] {
+        &self.chat
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    pub fn set_ready(&mut self, ready: bool) {
+        self.ready = ready;
+    }
+
+    /// Adds a line to the chat. A guest's lines show up once the host passed them on.
+    pub fn say(&mut self, text: &str) {
+        let text = clip(text.trim(), MAX_CHAT_LENGTH).to_string();
+        if text.is_empty() {
+            return;
+        }
+        match &mut self.role {
+            Role::Host { .. } => self.chat.push(ChatLine {
+                name: self.name.clone(),
+                text,
+            }),
+            Role::Guest { unheard, .. } => unheard.push_back(text),
+        }
+    }
+
+    /// Call every frame, `true` once the match starts. Fails when the lobby turns this player
+    /// away or never answers.
+    pub fn update(&mut self, seconds: f32) -> io::Result<bool> {
+        if self.started {
+            return Ok(true);
+        }
+        while !self.started {
+            let (packet, from) = match receive(&self.socket)? {
+                Some(packet) => packet,
+                None => break,
+            };
+            let message = match Message::from_bytes(&packet) {
+                Some(message) => message,
+                None => continue,
+            };
+            if self.is_host() {
+                self.host_receive(from, message)?;
+            } else {
+                self.guest_receive(from, message)?;
+            }
+        }
+
+        let mut host_left = false;
+        match &mut self.role {
+            Role::Host { guest } => {
+                if let Some(guest) = guest.as_mut() {
+                    guest.silence += seconds;
+                }
+                if matches!(guest, Some(guest) if guest.silence > TIMEOUT) {
+                    *guest = None;
+                }
+                if matches!(guest, Some(guest) if guest.member.ready && self.ready) {
+                    self.started = true;
+                }
+            }
+            Role::Guest {
+                entered, silence, ..
+            } => {
+                *silence += seconds;
+                if *silence > TIMEOUT && !*entered {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "the lobby did not answer",
+                    ));
+                }
+                host_left = *silence > TIMEOUT;
+            }
+        }
+        if host_left {
+            self.take_over()?;
+        }
+
+        self.since_sent += seconds;
+        if self.since_sent >= SEND_INTERVAL && !self.started {
+            self.since_sent = 0.0;
+            match &self.role {
+                Role::Host { guest: Some(guest) } => {
+                    send(&self.socket, &self.description(Some(guest)), guest.address)?
+                }
+                Role::Host { guest: None } => {}
+                Role::Guest {
+                    host,
+                    unheard,
+                    heard,
+                    ..
+                } => {
+                    let member = Message::Member {
+                        name: self.name.clone(),
+                        ready: self.ready,
+                        said: *heard,
+                        line: unheard.front().cloned(),
+                    };
+                    send(&self.socket, &member, *host)?
+                }
+            }
+        }
+        Ok(self.started)
+    }
+
+    /// Tells the others this player is gone. A guest then takes a hosted lobby over.
+    pub fn leave(&mut self) -> io::Result<()> {
+        match &self.role {
+            Role::Host { guest: Some(guest) } => {
+                send(&self.socket, &Message::Disconnect, guest.address)
+            }
+            Role::Host { guest: None } => Ok(()),
+            Role::Guest { host, .. } => send(&self.socket, &Message::Disconnect, *host),
+        }
+    }
+
+    /// The handshake of the match both players got ready for, on the lobby's socket. The host
+    /// plays the left paddle. Only call once `update` returned `true`.
+    pub fn into_handshake(self) -> io::Result<Handshake<UdpTransport>> {
+        Ok(match self.role {
+            Role::Host { guest } => {
+                let guest = guest.expect("the match has not started, there is no guest");
+                Handshake::host(
+                    UdpTransport::new(self.socket, Some(guest.address))?,
+                    self.rules,
+                    self.speed_up,
+                )
+            }
+            Role::Guest { host, .. } => {
+                Handshake::join(UdpTransport::new(self.socket, Some(host))?)
+            }
+        })
+    }
+
+    fn host_receive(&mut self, from: SocketAddr, message: Message) -> io::Result<()> {
+        let guest = match &mut self.role {
+            Role::Host { guest } => guest,
+            Role::Guest { .. } => return Ok(()),
+        };
+        match message {
+            Message::Discover => send(&self.socket, &self.description(None), from),
+            Message::Member {
+                name,
+                ready,
+                said,
+                line,
+            } => {
+                if matches!(guest, Some(guest) if guest.address != from) {
+                    return send(&self.socket, &Message::Disconnect, from);
+                }
+                let guest = guest.get_or_insert_with(|| Guest {
+                    address: from,
+                    member: LobbyMember {
+                        name: String::new(),
+                        ready: false,
+                    },
+                    heard: 0,
+                    silence: 0.0,
+                });
+                guest.member = LobbyMember {
+                    name: clip(&name, MAX_NAME_LENGTH).to_string(),
+                    ready,
+                };
+                guest.silence = 0.0;
+                if let Some(text) = line {
+                    // Each line counts once, however often the guest repeats it.
+                    if said == guest.heard {
+                        guest.heard += 1;
+                        self.chat.push(ChatLine {
+                            name: guest.member.name.clone(),
+                            text: clip(&text, MAX_CHAT_LENGTH).to_string(),
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Message::Disconnect => {
+                if matches!(guest, Some(guest) if guest.address == from) {
+                    *guest = None;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn guest_receive(&mut self, from: SocketAddr, message: Message) -> io::Result<()> {
+        let mut host_left = false;
+        if let Role::Guest {
+            host,
+            entered,
+            members,
+            silence,
+            unheard,
+            heard,
+            chat_received,
+        } = &mut self.role
+        {
+            if from != *host {
+                return Ok(());
+            }
+            match message {
+                Message::Lobby {
+                    members: roster,
+                    rules,
+                    speed_up,
+                    chat_start,
+                    chat,
+                    heard: host_heard,
+                    ..
+                } => {
+                    *entered = true;
+                    *silence = 0.0;
+                    *members = roster;
+                    self.rules = rules;
+                    self.speed_up = speed_up;
+                    while *heard < host_heard && unheard.pop_front().is_some() {
+                        *heard += 1;
+                    }
+                    for (number, line) in (chat_start..).zip(chat) {
+                        if number >= *chat_received {
+                            self.chat.push(line);
+                            *chat_received = number + 1;
+                        }
+                    }
+                }
+                // The host started the match's handshake.
+                Message::Hello { .. } => self.started = true,
+                Message::Disconnect if !*entered => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "the lobby is full",
+                    ))
+                }
+                Message::Disconnect => host_left = true,
+                _ => {}
+            }
+        }
+        if host_left {
+            self.take_over()?;
+        }
+        Ok(())
+    }
+
+    /// The host left, this player hosts the lobby from now on.
+    fn take_over(&mut self) -> io::Result<()> {
+        self.socket = bind_lobby_port()?;
+        self.role = Role::Host { guest: None };
+        Ok(())
+    }
+
+    /// What the host tells about the lobby. Only the guest gets the chat.
+    fn description(&self, guest: Option<&Guest>) -> Message {
+        let chat = match guest {
+            Some(_) => &self.chat[self.chat.len().saturating_sub(CHAT_LINES_PER_PACKET)..],
+            None => &[],
+        };
+        Message::Lobby {
+            id: self.id,
+            members: self.members(),
+            rules: self.rules.clone(),
+            speed_up: self.speed_up.clone(),
+            chat_start: (self.chat.len() - chat.len()) as u64,
+            chat: chat.to_vec(),
+            heard: guest.map_or(0, |guest| guest.heard),
+        }
+    }
+}
+
+/// Binds the first free lobby port, so finders know where to ask.
+fn bind_lobby_port() -> io::Result<UdpSocket> {
+    let socket = (LOBBY_PORT..LOBBY_PORT + LOBBY_PORTS)
+        .find_map(|port| UdpSocket::bind(("0.0.0.0", port)).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::AddrInUse, "every lobby port is taken"))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+fn send(socket: &UdpSocket, message: &Message, to: SocketAddr) -> io::Result<()> {
+    match socket.send_to(&message.to_bytes(), to) {
+        Ok(_) => Ok(()),
+        // Lost like any other packet, someone gone for good times out.
+        Err(error)
+            if error.kind() == io::ErrorKind::WouldBlock
+                || error.kind() == io::ErrorKind::ConnectionRefused =>
+        {
+            Ok(())
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The next packet and its sender, `None` while there is nothing to read.
+fn receive(socket: &UdpSocket) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    match socket.recv_from(&mut buffer) {
+        Ok((length, from)) => Ok(Some((buffer[..length].to_vec(), from))),
+        Err(error) => match error.kind() {
+            io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset => Ok(None),
+            _ => Err(error),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Side;
+    use std::thread;
+    use std::time::Duration;
+
+    const FRAME: f32 = 0.005;
+
+    fn member(name: &str, ready: bool) -> LobbyMember {
+        LobbyMember {
+            name: name.to_string(),
+            ready,
+        }
+    }
+
+    /// Runs frames of all `lobbies` until `done` holds.
+    fn run_until(lobbies: &mut [&mut Lobby], mut done: impl FnMut(&[&mut Lobby]) -> bool) {
+        for _ in 0..5000 {
+            for lobby in lobbies.iter_mut() {
+                lobby.update(FRAME).unwrap();
+            }
+            if done(lobbies) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("the lobbies never got there");
+    }
+
+    #[test]
+    fn players_find_a_lobby_chat_and_start_a_match() {
+        let rules = MatchRules {
+            points_to_win: 3,
+            ..MatchRules::default()
+        };
+        let mut alice = Lobby::host("Alice", rules.clone(), SpeedUp::default()).unwrap();
+        let mut finder = LobbyFinder::new().unwrap();
+        let mut listing = None;
+        run_until(&mut [&mut alice], |lobbies| {
+            finder.update(FRAME).unwrap();
+            listing = finder
+                .lobbies()
+                .iter()
+                .find(|lobby| lobby.id == lobbies[0].id())
+                .cloned();
+            listing.is_some()
+        });
+        let listing = listing.unwrap();
+        assert_eq!(listing.members, vec![member("Alice", false)]);
+        assert_eq!(listing.rules, rules);
+
+        let mut bob = Lobby::join("Bob", &listing).unwrap();
+        alice.say("hello");
+        bob.say("hi");
+        bob.say("  ");
+        bob.say("ready?");
+        run_until(&mut [&mut alice, &mut bob], |lobbies| {
+            lobbies.iter().all(|lobby| lobby.chat().len() == 3)
+        });
+        assert_eq!(alice.chat(), bob.chat());
+        assert_eq!(bob.chat()[0].name, "Alice");
+        assert_eq!(bob.chat()[2].text, "ready?");
+        assert_eq!(
+            bob.members(),
+            vec![member("Alice", false), member("Bob", false)]
+        );
+
+        alice.set_ready(true);
+        bob.set_ready(true);
+        run_until(&mut [&mut alice, &mut bob], |lobbies| lobbies[0].started);
+
+        // The guest starts once the host's handshake knocks.
+        let mut host = alice.into_handshake().unwrap();
+        let mut bob = Some(bob);
+        let mut guest = None;
+        let mut done = (false, false);
+        for _ in 0..5000 {
+            done.0 |= host.poll().unwrap();
+            if let Some(lobby) = &mut bob {
+                if lobby.update(FRAME).unwrap() {
+                    guest = Some(bob.take().unwrap().into_handshake().unwrap());
+                }
+            }
+            if let Some(guest) = &mut guest {
+                done.1 |= guest.poll().unwrap();
+            }
+            if done == (true, true) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(done, (true, true));
+        let (host, guest) = (host.into_session(), guest.unwrap().into_session());
+        assert_eq!(host.local_side(), Side::Left);
+        assert_eq!(guest.local_side(), Side::Right);
+        assert_eq!(guest.simulation().rules, rules);
+    }
+
+    #[test]
+    fn the_guest_takes_over_when_the_host_leaves() {
+        let mut alice = Lobby::host("Alice", MatchRules::default(), SpeedUp::default()).unwrap();
+        let listing = LobbyListing {
+            address: SocketAddr::from(([127, 0, 0, 1], alice.socket.local_addr().unwrap().port())),
+            id: alice.id(),
+            members: alice.members(),
+            rules: MatchRules::default(),
+            speed_up: SpeedUp::default(),
+            silence: 0.0,
+        };
+        let mut bob = Lobby::join("Bob", &listing).unwrap();
+        alice.say("brb");
+        run_until(&mut [&mut alice, &mut bob], |lobbies| {
+            lobbies[1].chat().len() == 1
+        });
+
+        alice.leave().unwrap();
+        drop(alice);
+        run_until(&mut [&mut bob], |lobbies| lobbies[0].is_host());
+        assert_eq!(bob.id(), listing.id);
+        assert_eq!(bob.members(), vec![member("Bob", false)]);
+        assert_eq!(bob.chat()[0].text, "brb");
+
+        // Others can still find the lobby, now with the new host.
+        let mut carol = Lobby::join(
+            "Carol",
+            &LobbyListing {
+                address: SocketAddr::from((
+                    [127, 0, 0, 1],
+                    bob.socket.local_addr().unwrap().port(),
+                )),
+                ..listing
+            },
+        )
+        .unwrap();
+        run_until(&mut [&mut bob, &mut carol], |lobbies| {
+            lobbies[1].members().len() == 2
+        });
+        assert_eq!(carol.members()[0], member("Bob", false));
+        assert_eq!(carol.chat()[0].text, "brb");
+    }
+}
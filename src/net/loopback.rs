@@ -0,0 +1,92 @@
+use crate::net::Transport;
+use crate::utils::Rng;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// How badly the in-process network behaves.
+#[derive(Clone, Debug, Default)]
+pub struct LoopbackConfig {
+    /// Seconds every packet takes.
+    pub latency: f32,
+    /// Up to this many seconds on top of `latency`, packets overtake each other.
+    pub jitter: f32,
+    /// Share of packets that never arrive, from 0 to 1.
+    pub packet_loss: f32,
+    /// Seed of the lost packets and the jitter, so a test sees the same network every run.
+    pub seed: u64,
+}
+
+struct Link {
+    config: LoopbackConfig,
+    rng: Rng,
+    /// Seconds since the network was created.
+    now: f32,
+    /// Packets on their way to each end with the time they arrive.
+    in_flight: [Vec<(f32, Vec<u8>)>; 2],
+}
+
+/// A network between two transports in the same process. Time only passes through `advance`,
+/// which makes latency and packet loss reproducible in tests.
+#[derive(Clone)]
+pub struct LoopbackNetwork {
+    link: Arc<Mutex<Link>>,
+}
+impl LoopbackNetwork {
+    pub fn new(config: LoopbackConfig) -> LoopbackNetwork {
+        LoopbackNetwork {
+            link: Arc::new(Mutex::new(Link {
+                rng: Rng::new(config.seed),
+                config,
+                now: 0.0,
+                in_flight: [Vec::new(), Vec::new()],
+            })),
+        }
+    }
+
+    /// Both ends of the network.
+    pub fn transports(&self) -> (LoopbackTransport, LoopbackTransport) {
+        (
+            LoopbackTransport {
+                link: self.link.clone(),
+                end: 0,
+            },
+            LoopbackTransport {
+                link: self.link.clone(),
+                end: 1,
+            },
+        )
+    }
+
+    pub fn advance(&self, seconds: f32) {
+        self.link.lock().unwrap().now += seconds;
+    }
+}
+
+pub struct LoopbackTransport {
+    link: Arc<Mutex<Link>>,
+    end: usize,
+}
+impl Transport for LoopbackTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        let mut link = self.link.lock().unwrap();
+        if link.rng.next_f32() < link.config.packet_loss {
+            return Ok(());
+        }
+        let arrival = link.now + link.config.latency + link.config.jitter * link.rng.next_f32();
+        link.in_flight[1 - self.end].push((arrival, packet.to_vec()));
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut link = self.link.lock().unwrap();
+        let now = link.now;
+        let in_flight = &mut link.in_flight[self.end];
+        let next = in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, (arrival, _))| *arrival <= now)
+            .min_by(|(_, (a, _)), (_, (b, _))| a.partial_cmp(b).unwrap())
+            .map(|(index, _)| index);
+        Ok(next.map(|index| in_flight.remove(index).1))
+    }
+}
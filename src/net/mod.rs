@@ -0,0 +1,60 @@
+//! Online matches. Between two peers, each peer runs the whole simulation itself and only the
+//! paddle inputs travel over the network. Inputs of the other peer that did not arrive yet are
+//! predicted, and the simulation is rolled back and run again when a prediction was wrong.
+//!
+//! On a dedicated server only the server simulates. Players send it their inputs, and it sends
+//! the state after every tick to the players and any spectators. A game can also broadcast its
+//! own match like that, to spectators watching over TCP.
+//!
+//! Players on the same local network can also meet in a lobby first, which they find with a
+//! broadcast, and start a match between two peers from there.
+
+mod broadcast;
+pub use self::broadcast::Broadcaster;
+
+mod client;
+pub use self::client::{ServerConnection, SnapshotBuffer, INTERPOLATION_DELAY};
+
+mod handshake;
+pub use self::handshake::Handshake;
+
+mod lobby;
+pub use self::lobby::{ChatLine, Lobby, LobbyFinder, LobbyListing, LobbyMember, MAX_CHAT_LENGTH};
+
+mod loopback;
+pub use self::loopback::{LoopbackConfig, LoopbackNetwork, LoopbackTransport};
+
+mod protocol;
+pub use self::protocol::{Message, PROTOCOL_VERSION};
+
+mod rollback;
+pub use self::rollback::{RollbackSession, MAX_PREDICTION_TICKS};
+
+mod server;
+pub use self::server::MatchServer;
+
+mod tcp;
+pub use self::tcp::TcpTransport;
+
+mod udp;
+pub use self::udp::UdpTransport;
+
+use std::io;
+
+/// Sends and receives whole packets to and from the other peer. Delivery is unreliable: packets
+/// can get lost, duplicated or arrive out of order.
+pub trait Transport: Send + Sync {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()>;
+
+    /// The next packet that arrived, `None` if there is nothing to read right now. Never blocks.
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        (**self).send(packet)
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        (**self).receive()
+    }
+}
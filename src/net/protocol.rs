@@ -0,0 +1,535 @@
+use crate::net::{ChatLine, LobbyMember};
+use crate::physics::SpeedUp;
+use crate::replay::{read_settings, write_settings, Snapshot};
+use crate::rules::MatchRules;
+use crate::simulation::{Bounce, PaddleInput, Side, SimEvent};
+use crate::utils::{ByteReader, Truncated};
+
+const MAGIC: &[u8; 4] = b"PONG";
+/// Peers only talk to peers of the same version, anything else would desync.
+pub const PROTOCOL_VERSION: u16 = 4;
+
+const JOIN: u8 = 0;
+const HELLO: u8 = 1;
+const READY: u8 = 2;
+const INPUTS: u8 = 3;
+const CONNECT: u8 = 4;
+const WELCOME: u8 = 5;
+const INPUT: u8 = 6;
+const STATE: u8 = 7;
+const DISCONNECT: u8 = 8;
+const DISCOVER: u8 = 9;
+const LOBBY: u8 = 10;
+const MEMBER: u8 = 11;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    /// Sent by the joining peer until the host answers, the host learns its address from it.
+    Join,
+    /// Sent by the hosting peer until the other one answers. The host decides how the match is
+    /// played.
+    Hello {
+        rules: MatchRules,
+        speed_up: SpeedUp,
+    },
+    /// The joining peer took the settings of a `Hello`.
+    Ready,
+    /// The sender's inputs from `first_tick` on, and how many of the receiver's inputs the
+    /// sender has without gaps.
+    Inputs {
+        first_tick: u64,
+        inputs: Vec<PaddleInput>,
+        received: u64,
+    },
+    /// A client asking a dedicated server to play or to watch. Repeated until the server
+    /// answers, and by spectators now and then to show they are still watching.
+    Connect { spectator: bool },
+    /// The server's answer to `Connect`: the side the client plays, `None` for spectators.
+    Welcome {
+        side: Option<Side>,
+        rules: MatchRules,
+        speed_up: SpeedUp,
+    },
+    /// A player's current input. Only the newest counts, older `sequence` numbers are dropped.
+    Input { sequence: u64, input: PaddleInput },
+    /// The server's match after its tick `server_tick`, sent to every client after every tick.
+    State {
+        server_tick: u64,
+        snapshot: Snapshot,
+        winner: Option<Side>,
+        /// What happened during that tick.
+        events: Vec<SimEvent>,
+    },
+    /// A client leaving the server, or anyone leaving a lobby. A lobby's host also sends it to
+    /// guests that cannot get in.
+    Disconnect,
+    /// Asks every lobby on the local network to describe itself.
+    Discover,
+    /// A lobby's host describing the lobby, to anyone looking for one and every few frames to
+    /// its guest. The `chat` lines are the host's lines from number `chat_start` on, `heard`
+    /// is how many of the guest's own lines the host took.
+    Lobby {
+        id: u64,
+        members: Vec<LobbyMember>,
+        rules: MatchRules,
+        speed_up: SpeedUp,
+        chat_start: u64,
+        chat: Vec<ChatLine>,
+        heard: u64,
+    },
+    /// A guest asking to enter a lobby, then telling its host about itself every few frames.
+    /// `line` is the guest's chat line number `said`, repeated until the host heard it.
+    Member {
+        name: String,
+        ready: bool,
+        said: u64,
+        line: Option<String>,
+    },
+}
+impl Message {
+    /// Little endian, like replays. At most 255 inputs fit into one packet.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        match self {
+            Message::Join => bytes.push(JOIN),
+            Message::Hello { rules, speed_up } => {
+                bytes.push(HELLO);
+                write_settings(&mut bytes, rules, speed_up);
+            }
+            Message::Ready => bytes.push(READY),
+            Message::Inputs {
+                first_tick,
+                inputs,
+                received,
+            } => {
+                bytes.push(INPUTS);
+                bytes.extend_from_slice(&first_tick.to_le_bytes());
+                bytes.extend_from_slice(&received.to_le_bytes());
+                bytes.push(inputs.len().min(255) as u8);
+                bytes.extend(inputs.iter().take(255).map(|input| input.movement as u8));
+            }
+            Message::Connect { spectator } => {
+                bytes.push(CONNECT);
+                bytes.push(*spectator as u8);
+            }
+            Message::Welcome {
+                side,
+                rules,
+                speed_up,
+            } => {
+                bytes.push(WELCOME);
+                bytes.push(side_code(*side));
+                write_settings(&mut bytes, rules, speed_up);
+            }
+            Message::Input { sequence, input } => {
+                bytes.push(INPUT);
+                bytes.extend_from_slice(&sequence.to_le_bytes());
+                bytes.push(input.movement as u8);
+            }
+            Message::State {
+                server_tick,
+                snapshot,
+                winner,
+                events,
+            } => {
+                bytes.push(STATE);
+                bytes.extend_from_slice(&server_tick.to_le_bytes());
+                write_snapshot(&mut bytes, snapshot);
+                bytes.push(side_code(*winner));
+                bytes.push(events.len().min(255) as u8);
+                for event in events.iter().take(255) {
+                    let (kind, side, bounce) = match event {
+                        SimEvent::PaddleHit(side, bounce) => (0, Some(*side), Some(bounce)),
+                        SimEvent::WallHit(bounce) => (1, None, Some(bounce)),
+                        SimEvent::Scored(side) => (2, Some(*side), None),
+                        SimEvent::MatchOver(side) => (3, Some(*side), None),
+                        SimEvent::LostLife(side) => (4, Some(*side), None),
+                        SimEvent::Eliminated(side) => (5, Some(*side), None),
+                    };
+                    bytes.push(kind);
+                    bytes.push(side_code(side));
+                    if let Some(bounce) = bounce {
+                        write_bounce(&mut bytes, bounce);
+                    }
+                }
+            }
+            Message::Disconnect => bytes.push(DISCONNECT),
+            Message::Discover => bytes.push(DISCOVER),
+            Message::Lobby {
+                id,
+                members,
+                rules,
+                speed_up,
+                chat_start,
+                chat,
+                heard,
+            } => {
+                bytes.push(LOBBY);
+                bytes.extend_from_slice(&id.to_le_bytes());
+                bytes.push(members.len().min(255) as u8);
+                for member in members.iter().take(255) {
+                    write_text(&mut bytes, &member.name);
+                    bytes.push(member.ready as u8);
+                }
+                write_settings(&mut bytes, rules, speed_up);
+                bytes.extend_from_slice(&chat_start.to_le_bytes());
+                bytes.push(chat.len().min(255) as u8);
+                for line in chat.iter().take(255) {
+                    write_text(&mut bytes, &line.name);
+                    write_text(&mut bytes, &line.text);
+                }
+                bytes.extend_from_slice(&heard.to_le_bytes());
+            }
+            Message::Member {
+                name,
+                ready,
+                said,
+                line,
+            } => {
+                bytes.push(MEMBER);
+                write_text(&mut bytes, name);
+                bytes.push(*ready as u8);
+                bytes.extend_from_slice(&said.to_le_bytes());
+                if let Some(line) = line {
+                    write_text(&mut bytes, line);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// `None` for anything that is not a complete packet of this protocol version. Such
+    /// packets are dropped like lost ones.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Message> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(MAGIC.len()).ok()? != &MAGIC[..] || reader.u16().ok()? != PROTOCOL_VERSION {
+            return None;
+        }
+        let message = match reader.u8().ok()? {
+            JOIN => Message::Join,
+            HELLO => {
+                let (rules, speed_up) = read_settings(&mut reader).ok()?;
+                Message::Hello { rules, speed_up }
+            }
+            READY => Message::Ready,
+            INPUTS => {
+                let first_tick = reader.u64().ok()?;
+                let received = reader.u64().ok()?;
+                let count = reader.u8().ok()?;
+                let inputs = reader
+                    .take(usize::from(count))
+                    .ok()?
+                    .iter()
+                    .map(|movement| PaddleInput {
+                        movement: *movement as i8,
+                    })
+                    .collect();
+                Message::Inputs {
+                    first_tick,
+                    inputs,
+                    received,
+                }
+            }
+            CONNECT => Message::Connect {
+                spectator: reader.u8().ok()? != 0,
+            },
+            WELCOME => {
+                let side = read_side(&mut reader).ok()??;
+                let (rules, speed_up) = read_settings(&mut reader).ok()?;
+                Message::Welcome {
+                    side,
+                    rules,
+                    speed_up,
+                }
+            }
+            INPUT => Message::Input {
+                sequence: reader.u64().ok()?,
+                input: PaddleInput {
+                    movement: reader.u8().ok()? as i8,
+                },
+            },
+            STATE => {
+                let server_tick = reader.u64().ok()?;
+                let snapshot = read_snapshot(&mut reader).ok()?;
+                let winner = read_side(&mut reader).ok()??;
+                let mut events = Vec::new();
+                for _ in 0..reader.u8().ok()? {
+                    let kind = reader.u8().ok()?;
+                    let side = read_side(&mut reader).ok()?;
+                    events.push(match (kind, side) {
+                        (0, Some(Some(side))) => {
+                            SimEvent::PaddleHit(side, read_bounce(&mut reader).ok()?)
+                        }
+                        (1, _) => SimEvent::WallHit(read_bounce(&mut reader).ok()?),
+                        (2, Some(Some(side))) => SimEvent::Scored(side),
+                        (3, Some(Some(side))) => SimEvent::MatchOver(side),
+                        (4, Some(Some(side))) => SimEvent::LostLife(side),
+                        (5, Some(Some(side))) => SimEvent::Eliminated(side),
+                        _ => return None,
+                    });
+                }
+                Message::State {
+                    server_tick,
+                    snapshot,
+                    winner,
+                    events,
+                }
+            }
+            DISCONNECT => Message::Disconnect,
+            DISCOVER => Message::Discover,
+            LOBBY => {
+                let id = reader.u64().ok()?;
+                let mut members = Vec::new();
+                for _ in 0..reader.u8().ok()? {
+                    members.push(LobbyMember {
+                        name: read_text(&mut reader)?,
+                        ready: reader.u8().ok()? != 0,
+                    });
+                }
+                let (rules, speed_up) = read_settings(&mut reader).ok()?;
+                let chat_start = reader.u64().ok()?;
+                let mut chat = Vec::new();
+                for _ in 0..reader.u8().ok()? {
+                    chat.push(ChatLine {
+                        name: read_text(&mut reader)?,
+                        text: read_text(&mut reader)?,
+                    });
+                }
+                Message::Lobby {
+                    id,
+                    members,
+                    rules,
+                    speed_up,
+                    chat_start,
+                    chat,
+                    heard: reader.u64().ok()?,
+                }
+            }
+            MEMBER => {
+                let name = read_text(&mut reader)?;
+                let ready = reader.u8().ok()? != 0;
+                let said = reader.u64().ok()?;
+                let line = if reader.remaining().is_empty() {
+                    None
+                } else {
+                    Some(read_text(&mut reader)?)
+                };
+                Message::Member {
+                    name,
+                    ready,
+                    said,
+                    line,
+                }
+            }
+            _ => return None,
+        };
+        if reader.remaining().is_empty() {
+            Some(message)
+        } else {
+            None
+        }
+    }
+}
+
+fn side_code(side: Option<Side>) -> u8 {
+    side.map_or(0, |side| side.index() as u8 + 1)
+}
+
+/// `None` inside for a code that is no side at all.
+fn read_side(reader: &mut ByteReader<'_>) -> Result<Option<Option<Side>>, Truncated> {
+    Ok(match reader.u8()? {
+        0 => Some(None),
+        code @ 1..=4 => Some(Some(Side::ALL[usize::from(code) - 1])),
+        _ => None,
+    })
+}
+
+/// The longest start of `text` that fits into `max_bytes` without splitting a character.
+pub(super) fn clip(text: &str, max_bytes: usize) -> &str {
+    let mut end = text.len().min(max_bytes);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// UTF-8 with a length byte, longer texts are cut off.
+fn write_text(bytes: &mut Vec<u8>, text: &str) {
+    let text = clip(text, 255);
+    bytes.push(text.len() as u8);
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn read_text(reader: &mut ByteReader<'_>) -> Option<String> {
+    let length = reader.u8().ok()?;
+    String::from_utf8(reader.take(usize::from(length)).ok()?.to_vec()).ok()
+}
+
+fn write_snapshot(bytes: &mut Vec<u8>, snapshot: &Snapshot) {
+    bytes.extend_from_slice(&snapshot.tick.to_le_bytes());
+    for positions in &[&snapshot.paddles, &snapshot.balls] {
+        bytes.push(positions.len().min(255) as u8);
+        for position in positions.iter().take(255) {
+            bytes.extend_from_slice(&position[0].to_le_bytes());
+            bytes.extend_from_slice(&position[1].to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(&snapshot.score_left.to_le_bytes());
+    bytes.extend_from_slice(&snapshot.score_right.to_le_bytes());
+    bytes.push(snapshot.lives.len().min(255) as u8);
+    for lives in snapshot.lives.iter().take(255) {
+        bytes.extend_from_slice(&lives.to_le_bytes());
+    }
+}
+
+fn write_bounce(bytes: &mut Vec<u8>, bounce: &Bounce) {
+    bytes.extend_from_slice(&bounce.position[0].to_le_bytes());
+    bytes.extend_from_slice(&bounce.position[1].to_le_bytes());
+    bytes.extend_from_slice(&bounce.speed.to_le_bytes());
+}
+
+fn read_bounce(reader: &mut ByteReader<'_>) -> Result<Bounce, Truncated> {
+    Ok(Bounce {
+        position: [reader.f32()?, reader.f32()?],
+        speed: reader.f32()?,
+    })
+}
+
+fn read_snapshot(reader: &mut ByteReader<'_>) -> Result<Snapshot, Truncated> {
+    let tick = reader.u64()?;
+    let mut read_positions = || -> Result<Vec<[f32; 2]>, Truncated> {
+        let count = reader.u8()?;
+        (0..count)
+            .map(|_| Ok([reader.f32()?, reader.f32()?]))
+            .collect()
+    };
+    let paddles = read_positions()?;
+    let balls = read_positions()?;
+    let score_left = reader.i32()?;
+    let score_right = reader.i32()?;
+    let lives = (0..reader.u8()?)
+        .map(|_| reader.i32())
+        .collect::<Result<_, _>>()?;
+    Ok(Snapshot {
+        tick,
+        paddles,
+        balls,
+        score_left,
+        score_right,
+        lives,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::MatchMode;
+
+    #[test]
+    fn messages_survive_the_wire() {
+        let messages = [
+            Message::Join,
+            Message::Hello {
+                rules: MatchRules {
+                    points_to_win: 3,
+                    win_by_two: true,
+                    time_limit: Some(90.0),
+                    mode: MatchMode::Doubles,
+                    lives: 5,
+                },
+                speed_up: SpeedUp::default(),
+            },
+            Message::Ready,
+            Message::Inputs {
+                first_tick: 1234,
+                inputs: vec![PaddleInput { movement: -127 }, PaddleInput { movement: 64 }],
+                received: 1200,
+            },
+            Message::Connect { spectator: true },
+            Message::Welcome {
+                side: Some(Side::Right),
+                rules: MatchRules::default(),
+                speed_up: SpeedUp::default(),
+            },
+            Message::Input {
+                sequence: 99,
+                input: PaddleInput { movement: 127 },
+            },
+            Message::State {
+                server_tick: 5000,
+                snapshot: Snapshot {
+                    tick: 4000,
+                    paddles: vec![[2.0, 50.0], [98.0, 40.5]],
+                    balls: vec![[50.0, 50.0]],
+                    score_left: 4,
+                    score_right: 2,
+                    lives: vec![3, 0, 1, 2],
+                },
+                winner: None,
+                events: vec![
+                    SimEvent::WallHit(Bounce {
+                        position: [30.0, 2.0],
+                        speed: 90.5,
+                    }),
+                    SimEvent::PaddleHit(
+                        Side::Right,
+                        Bounce {
+                            position: [94.0, 61.25],
+                            speed: 120.0,
+                        },
+                    ),
+                    SimEvent::Scored(Side::Left),
+                    SimEvent::Eliminated(Side::Bottom),
+                ],
+            },
+            Message::Disconnect,
+            Message::Discover,
+            Message::Lobby {
+                id: 0x1234_5678_9abc,
+                members: vec![
+                    LobbyMember {
+                        name: "Zoë".to_string(),
+                        ready: true,
+                    },
+                    LobbyMember {
+                        name: "Bob".to_string(),
+                        ready: false,
+                    },
+                ],
+                rules: MatchRules::default(),
+                speed_up: SpeedUp::default(),
+                chat_start: 3,
+                chat: vec![ChatLine {
+                    name: "Bob".to_string(),
+                    text: "gl hf".to_string(),
+                }],
+                heard: 1,
+            },
+            Message::Member {
+                name: "Bob".to_string(),
+                ready: true,
+                said: 2,
+                line: Some("ready?".to_string()),
+            },
+            Message::Member {
+                name: "Bob".to_string(),
+                ready: false,
+                said: 3,
+                line: None,
+            },
+        ];
+        for message in &messages {
+            let bytes = message.to_bytes();
+            assert_eq!(Message::from_bytes(&bytes).as_ref(), Some(message));
+            assert_eq!(Message::from_bytes(&bytes[..bytes.len() - 1]), None);
+        }
+
+        let mut other_version = Message::Ready.to_bytes();
+        other_version[MAGIC.len()] += 1;
+        assert_eq!(Message::from_bytes(&other_version), None);
+        assert_eq!(Message::from_bytes(b"GET / HTTP/1.1"), None);
+        assert_eq!(clip("Zoë", 3), "Zo");
+    }
+}
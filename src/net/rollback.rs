@@ -0,0 +1,339 @@
+use crate::net::{Message, Transport};
+use crate::simulation::{PaddleInput, Side, SimEvent, Simulation, MAX_PLAYERS};
+use std::io;
+
+/// How many ticks a peer runs ahead of the last tick it has both inputs for. Beyond that it
+/// waits for the other peer, mispredictions would become too visible.
+pub const MAX_PREDICTION_TICKS: usize = 8;
+/// Every packet repeats all inputs the other peer has not acknowledged yet, up to this many,
+/// so a lost packet is made up for by the next one.
+const MAX_INPUTS_PER_PACKET: usize = 64;
+
+/// One peer's view of an online match. The local paddle's inputs are known right away, the
+/// remote paddle repeats its last known input until the real one arrives.
+pub struct RollbackSession<T> {
+    transport: T,
+    local_side: Side,
+    /// Indexed by tick.
+    local_inputs: Vec<PaddleInput>,
+    /// Indexed by tick, `None` until the input arrived.
+    remote_inputs: Vec<Option<PaddleInput>>,
+    /// All remote inputs of the ticks before this one arrived.
+    remote_received: usize,
+    /// The remote peer has all of our inputs before this tick.
+    acknowledged: usize,
+    /// The match after all ticks with both inputs known, it never has to be rolled back.
+    confirmed: Simulation,
+    confirmed_ticks: usize,
+    /// `confirmed` run on through the remaining local inputs, what the player sees.
+    predicted: Simulation,
+    /// The remote inputs `predicted` used, from `confirmed_ticks` on.
+    predictions: Vec<PaddleInput>,
+    rollbacks: u64,
+    ticks_since_received: u32,
+}
+impl<T: Transport> RollbackSession<T> {
+    /// Starts at the state of `simulation`, which has to be the same on both peers.
+    pub fn new(transport: T, local_side: Side, simulation: Simulation) -> RollbackSession<T> {
+        RollbackSession {
+            transport,
+            local_side,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            remote_received: 0,
+            acknowledged: 0,
+            confirmed: simulation.clone(),
+            confirmed_ticks: 0,
+            predicted: simulation,
+            predictions: Vec::new(),
+            rollbacks: 0,
+            ticks_since_received: 0,
+        }
+    }
+
+    pub fn local_side(&self) -> Side {
+        self.local_side
+    }
+
+    /// The current state including predictions, this is what to show.
+    pub fn simulation(&self) -> &Simulation {
+        &self.predicted
+    }
+
+    /// The state both peers agree on.
+    pub fn confirmed(&self) -> &Simulation {
+        &self.confirmed
+    }
+
+    /// Ticks run so far, unlike `Simulation::tick` this keeps counting after the match is over.
+    pub fn tick(&self) -> usize {
+        self.local_inputs.len()
+    }
+
+    /// Both inputs of every confirmed tick up to the end of the match, laid out like
+    /// `Replay::inputs`.
+    pub fn confirmed_inputs(&self) -> Vec<[PaddleInput; MAX_PLAYERS]> {
+        (0..self.confirmed.tick as usize)
+            .map(|tick| self.inputs(self.local_inputs[tick], self.remote_inputs[tick].unwrap()))
+            .collect()
+    }
+
+    /// How often a misprediction sent the simulation back.
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    /// Calls to `advance` since the last packet from the other peer.
+    pub fn ticks_since_received(&self) -> u32 {
+        self.ticks_since_received
+    }
+
+    /// Runs one tick with the local input and a prediction of the remote one. `None` if the
+    /// other peer is too far behind, the input then has to be given again next tick.
+    pub fn advance(&mut self, local_input: PaddleInput) -> io::Result<Option<Vec<SimEvent>>> {
+        self.ticks_since_received = self.ticks_since_received.saturating_add(1);
+        if self.local_inputs.len() - self.confirmed_ticks >= MAX_PREDICTION_TICKS {
+            // Keep sending, the other peer may be waiting for our inputs just as well.
+            self.send_inputs()?;
+            return Ok(None);
+        }
+
+        let tick = self.local_inputs.len();
+        let remote_input = self.remote_input(tick);
+        self.local_inputs.push(local_input);
+        self.predictions.push(remote_input);
+        let inputs = self.inputs(local_input, remote_input);
+        let events = self.predicted.step(&inputs);
+        self.send_inputs()?;
+        Ok(Some(events))
+    }
+
+    /// Reads everything the other peer sent. Confirms the ticks that have both inputs now and
+    /// runs the predicted ticks again if one of their predictions was wrong.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut mispredicted = false;
+        while let Some(packet) = self.transport.receive()? {
+            match Message::from_bytes(&packet) {
+                Some(Message::Inputs {
+                    first_tick,
+                    inputs,
+                    received,
+                }) => {
+                    self.ticks_since_received = 0;
+                    // Only inputs we sent can have arrived.
+                    self.acknowledged = self
+                        .acknowledged
+                        .max(received as usize)
+                        .min(self.local_inputs.len());
+                    // The other peer is never further ahead than this, anything beyond comes
+                    // from a broken packet or an earlier session on the same port.
+                    let horizon =
+                        self.local_inputs.len() + MAX_PREDICTION_TICKS + MAX_INPUTS_PER_PACKET;
+                    for (offset, input) in inputs.into_iter().enumerate() {
+                        let tick = match (first_tick as usize).checked_add(offset) {
+                            Some(tick) if tick < horizon => tick,
+                            _ => break,
+                        };
+                        if self.remote_inputs.len() <= tick {
+                            self.remote_inputs.resize(tick + 1, None);
+                        }
+                        if self.remote_inputs[tick].is_some() {
+                            continue;
+                        }
+                        self.remote_inputs[tick] = Some(input);
+                        let prediction = tick
+                            .checked_sub(self.confirmed_ticks)
+                            .and_then(|index| self.predictions.get(index));
+                        if matches!(prediction, Some(prediction) if *prediction != input) {
+                            mispredicted = true;
+                        }
+                    }
+                }
+                // The hosting peer missed our answer to its hello.
+                Some(Message::Hello { .. }) => self.transport.send(&Message::Ready.to_bytes())?,
+                _ => {}
+            }
+        }
+
+        while let Some(Some(_)) = self.remote_inputs.get(self.remote_received) {
+            self.remote_received += 1;
+        }
+        while self.confirmed_ticks < self.remote_received.min(self.local_inputs.len()) {
+            let tick = self.confirmed_ticks;
+            let inputs = self.inputs(self.local_inputs[tick], self.remote_inputs[tick].unwrap());
+            self.confirmed.step(&inputs);
+            self.confirmed_ticks += 1;
+            self.predictions.remove(0);
+        }
+
+        if mispredicted {
+            self.rollbacks += 1;
+            self.predicted = self.confirmed.clone();
+            self.predictions.clear();
+            for tick in self.confirmed_ticks..self.local_inputs.len() {
+                let remote_input = self.remote_input(tick);
+                self.predictions.push(remote_input);
+                let inputs = self.inputs(self.local_inputs[tick], remote_input);
+                self.predicted.step(&inputs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the inputs the other peer is missing again, also telling it that we are still
+    /// there while no ticks are run here.
+    pub fn send_inputs(&mut self) -> io::Result<()> {
+        let first_tick = self.acknowledged.min(self.local_inputs.len());
+        let last_tick = self
+            .local_inputs
+            .len()
+            .min(first_tick + MAX_INPUTS_PER_PACKET);
+        let message = Message::Inputs {
+            first_tick: first_tick as u64,
+            inputs: self.local_inputs[first_tick..last_tick].to_vec(),
+            received: self.remote_received as u64,
+        };
+        self.transport.send(&message.to_bytes())
+    }
+
+    /// The remote input of `tick` if it arrived, otherwise the last one that did.
+    fn remote_input(&self, tick: usize) -> PaddleInput {
+        self.remote_inputs[..self.remote_inputs.len().min(tick + 1)]
+            .iter()
+            .rev()
+            .find_map(|input| *input)
+            .unwrap_or_default()
+    }
+
+    /// Both inputs, indexed by `Side::index`. Online matches are always between the left and
+    /// the right paddle.
+    fn inputs(
+        &self,
+        local_input: PaddleInput,
+        remote_input: PaddleInput,
+    ) -> [PaddleInput; MAX_PLAYERS] {
+        let mut inputs = [PaddleInput::default(); MAX_PLAYERS];
+        let (left, right) = match self.local_side {
+            Side::Left => (local_input, remote_input),
+            _ => (remote_input, local_input),
+        };
+        inputs[Side::Left.index()] = left;
+        inputs[Side::Right.index()] = right;
+        inputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{Handshake, LoopbackConfig, LoopbackNetwork, LoopbackTransport};
+    use crate::physics::SpeedUp;
+    use crate::rules::MatchRules;
+    use crate::simulation::TICK_DELTA;
+
+    /// Changes direction every few ticks, differently for both sides.
+    fn scripted_input(side: Side, tick: usize) -> PaddleInput {
+        let phase = (tick / 17 + side.index() * 5) as u64;
+        let movement = (phase.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 61) as i8 % 3 - 1;
+        PaddleInput {
+            movement: movement * 127,
+        }
+    }
+
+    fn connect(
+        config: LoopbackConfig,
+    ) -> (LoopbackNetwork, [RollbackSession<LoopbackTransport>; 2]) {
+        let network = LoopbackNetwork::new(config);
+        let (host_transport, guest_transport) = network.transports();
+        let rules = MatchRules {
+            points_to_win: 3,
+            ..MatchRules::default()
+        };
+        let mut host = Handshake::host(host_transport, rules, SpeedUp::default());
+        let mut guest = Handshake::join(guest_transport);
+        let mut done = [false, false];
+        while done != [true, true] {
+            done = [host.poll().unwrap(), guest.poll().unwrap()];
+            network.advance(TICK_DELTA);
+        }
+        (network, [host.into_session(), guest.into_session()])
+    }
+
+    #[test]
+    fn peers_agree_despite_latency_and_packet_loss() {
+        let (network, mut peers) = connect(LoopbackConfig {
+            latency: 0.06,
+            jitter: 0.05,
+            packet_loss: 0.25,
+            seed: 42,
+        });
+
+        let mut frames = 0;
+        while peers.iter().any(|peer| peer.confirmed().winner.is_none()) {
+            for peer in &mut peers {
+                peer.poll().unwrap();
+                let input = scripted_input(peer.local_side(), peer.tick());
+                peer.advance(input).unwrap();
+            }
+            network.advance(TICK_DELTA);
+            frames += 1;
+            assert!(frames < 1_000_000, "match never ended");
+        }
+
+        let mut offline = peers[0].confirmed().clone();
+        offline.reset();
+        offline.serve();
+        let mut tick = 0;
+        while offline.winner.is_none() {
+            offline.step(&[
+                scripted_input(Side::Left, tick),
+                scripted_input(Side::Right, tick),
+            ]);
+            tick += 1;
+        }
+
+        assert!(peers.iter().any(|peer| peer.rollbacks() > 0));
+        assert_eq!(peers[0].confirmed_inputs(), peers[1].confirmed_inputs());
+        for peer in &peers {
+            assert_eq!(peer.confirmed().state_hash(), offline.state_hash());
+        }
+    }
+
+    #[test]
+    fn waits_for_a_silent_peer() {
+        let (_network, [mut host, _guest]) = connect(LoopbackConfig::default());
+        for _ in 0..MAX_PREDICTION_TICKS {
+            host.poll().unwrap();
+            assert!(host.advance(PaddleInput::default()).unwrap().is_some());
+        }
+        host.poll().unwrap();
+        assert!(host.advance(PaddleInput::default()).unwrap().is_none());
+        assert_eq!(host.tick(), MAX_PREDICTION_TICKS);
+    }
+
+    #[test]
+    fn ignores_inputs_for_ticks_far_ahead() {
+        let (network, [mut host, mut guest]) = connect(LoopbackConfig::default());
+        for first_tick in &[u64::MAX - 1, 1 << 40] {
+            let packet = Message::Inputs {
+                first_tick: *first_tick,
+                inputs: vec![PaddleInput::default(); 4],
+                received: 0,
+            };
+            guest.transport.send(&packet.to_bytes()).unwrap();
+        }
+        // Nor does it take an acknowledgement of inputs it never sent.
+        let packet = Message::Inputs {
+            first_tick: 0,
+            inputs: Vec::new(),
+            received: u64::MAX,
+        };
+        guest.transport.send(&packet.to_bytes()).unwrap();
+        network.advance(TICK_DELTA);
+
+        host.poll().unwrap();
+        assert!(host.remote_inputs.len() <= MAX_PREDICTION_TICKS + MAX_INPUTS_PER_PACKET);
+        assert_eq!(host.acknowledged, 0);
+        assert!(host.advance(PaddleInput::default()).unwrap().is_some());
+    }
+}
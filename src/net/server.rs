@@ -0,0 +1,307 @@
+use crate::net::Message;
+use crate::physics::SpeedUp;
+use crate::replay::Snapshot;
+use crate::rules::MatchRules;
+use crate::simulation::{PaddleInput, Side, Simulation, TICK_DELTA};
+
+/// Ticks a client may stay silent before the server forgets it.
+const TIMEOUT_TICKS: u64 = (10.0 / TICK_DELTA) as u64;
+/// Ticks between both players being there and the serve.
+const SERVE_DELAY_TICKS: u64 = (3.0 / TICK_DELTA) as u64;
+/// Ticks the final score stays up before the server waits for new players.
+const RESTART_DELAY_TICKS: u64 = (5.0 / TICK_DELTA) as u64;
+
+struct Client<A> {
+    address: A,
+    /// `None` for spectators.
+    side: Option<Side>,
+    last_heard: u64,
+}
+
+/// The authoritative side of a match on a dedicated server. It knows nothing about sockets:
+/// packets from clients go into `receive`, and after every `tick` the returned state goes out
+/// to all `clients`. `A` tells clients apart, a socket address on a real server.
+pub struct MatchServer<A> {
+    simulation: Simulation,
+    clients: Vec<Client<A>>,
+    /// Indexed by `Side::index`.
+    inputs: [PaddleInput; 2],
+    input_sequences: [u64; 2],
+    ticks: u64,
+    /// Tick at which the running phase started: the players being complete, or the match
+    /// ending.
+    phase_start: u64,
+}
+impl<A: Copy + PartialEq> MatchServer<A> {
+    pub fn new(rules: MatchRules, speed_up: SpeedUp) -> MatchServer<A> {
+        MatchServer {
+            simulation: Simulation::new(rules, speed_up),
+            clients: Vec::new(),
+            inputs: [PaddleInput::default(); 2],
+            input_sequences: [0; 2],
+            ticks: 0,
+            phase_start: 0,
+        }
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    /// Everyone the state goes to after a tick.
+    pub fn clients(&self) -> impl Iterator<Item = A> + '_ {
+        self.clients.iter().map(|client| client.address)
+    }
+
+    /// The side played by the client at `address`, `None` for spectators and strangers.
+    pub fn side_of(&self, address: A) -> Option<Side> {
+        self.client(address).and_then(|client| client.side)
+    }
+
+    /// Handles one packet from `address` and returns the answer to send back, if any.
+    pub fn receive(&mut self, address: A, message: Message) -> Option<Message> {
+        let ticks = self.ticks;
+        if let Some(client) = self
+            .clients
+            .iter_mut()
+            .find(|client| client.address == address)
+        {
+            client.last_heard = ticks;
+        }
+
+        match message {
+            Message::Connect { spectator } => {
+                let side = match self.client(address) {
+                    Some(client) => client.side,
+                    None => {
+                        let side = if spectator { None } else { self.free_side() };
+                        self.clients.push(Client {
+                            address,
+                            side,
+                            last_heard: ticks,
+                        });
+                        if let Some(side) = side {
+                            // A new player counts their inputs from the start again.
+                            self.input_sequences[side.index()] = 0;
+                            if self.free_side().is_none() {
+                                self.phase_start = ticks;
+                            }
+                        }
+                        side
+                    }
+                };
+                Some(Message::Welcome {
+                    side,
+                    rules: self.simulation.rules.clone(),
+                    speed_up: self.simulation.speed_up.clone(),
+                })
+            }
+            Message::Input { sequence, input } => {
+                if let Some(side) = self.side_of(address) {
+                    if sequence > self.input_sequences[side.index()] {
+                        self.input_sequences[side.index()] = sequence;
+                        self.inputs[side.index()] = input;
+                    }
+                }
+                None
+            }
+            Message::Disconnect => {
+                self.remove_clients(|client| client.address == address);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Advances the server by `TICK_DELTA` and returns the state to send to every client. The
+    /// match runs once both sides have a player, a few seconds after the last one joined.
+    pub fn tick(&mut self) -> Message {
+        self.ticks += 1;
+        let ticks = self.ticks;
+        self.remove_clients(|client| ticks - client.last_heard > TIMEOUT_TICKS);
+
+        let mut events = Vec::new();
+        if self.free_side().is_none() {
+            if self.simulation.winner.is_some() {
+                // The players keep their paddles for a rematch.
+                if ticks - self.phase_start >= RESTART_DELAY_TICKS {
+                    self.reset_match();
+                }
+            } else if !self.simulation.balls.is_empty() {
+                events = self.simulation.step(&self.inputs);
+                if self.simulation.winner.is_some() {
+                    self.phase_start = ticks;
+                }
+            } else if ticks - self.phase_start >= SERVE_DELAY_TICKS {
+                self.simulation.serve();
+            }
+        }
+
+        Message::State {
+            server_tick: ticks,
+            snapshot: Snapshot::capture(&self.simulation),
+            winner: self.simulation.winner,
+            events,
+        }
+    }
+
+    fn client(&self, address: A) -> Option<&Client<A>> {
+        self.clients.iter().find(|client| client.address == address)
+    }
+
+    fn free_side(&self) -> Option<Side> {
+        [Side::Left, Side::Right]
+            .iter()
+            .copied()
+            .find(|side| self.clients.iter().all(|client| client.side != Some(*side)))
+    }
+
+    /// Losing a player in the middle of a match ends it, the next one starts from zero.
+    fn remove_clients<F: Fn(&Client<A>) -> bool>(&mut self, remove: F) {
+        let freed = self
+            .clients
+            .iter()
+            .filter(|client| remove(client))
+            .filter_map(|client| client.side)
+            .collect::<Vec<_>>();
+        self.clients.retain(|client| !remove(client));
+        for side in &freed {
+            self.input_sequences[side.index()] = 0;
+        }
+        if !freed.is_empty() {
+            self.reset_match();
+        }
+    }
+
+    fn reset_match(&mut self) {
+        self.simulation.reset();
+        self.inputs = [PaddleInput::default(); 2];
+        self.phase_start = self.ticks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(server: &mut MatchServer<u32>, address: u32, spectator: bool) -> Option<Side> {
+        match server.receive(address, Message::Connect { spectator }) {
+            Some(Message::Welcome { side, .. }) => side,
+            answer => panic!("expected a welcome, got {:?}", answer),
+        }
+    }
+
+    #[test]
+    fn two_players_start_a_match_and_spectators_watch() {
+        let mut server = MatchServer::new(MatchRules::default(), SpeedUp::default());
+        assert_eq!(connect(&mut server, 1, false), Some(Side::Left));
+        assert_eq!(connect(&mut server, 2, true), None);
+        for _ in 0..SERVE_DELAY_TICKS * 2 {
+            server.tick();
+        }
+        assert!(
+            server.simulation().balls.is_empty(),
+            "served without opponent"
+        );
+
+        assert_eq!(connect(&mut server, 3, false), Some(Side::Right));
+        assert_eq!(connect(&mut server, 4, false), None, "no third paddle");
+        assert_eq!(connect(&mut server, 3, false), Some(Side::Right));
+        for _ in 0..SERVE_DELAY_TICKS {
+            server.tick();
+        }
+        assert_eq!(server.simulation().balls.len(), 1);
+        assert_eq!(server.clients().count(), 4);
+
+        let right_y = server.simulation().paddles[1].position[1];
+        server.receive(
+            3,
+            Message::Input {
+                sequence: 1,
+                input: PaddleInput { movement: -127 },
+            },
+        );
+        // Spectators cannot move paddles.
+        server.receive(
+            4,
+            Message::Input {
+                sequence: 2,
+                input: PaddleInput { movement: 127 },
+            },
+        );
+        match server.tick() {
+            Message::State { snapshot, .. } => assert!(snapshot.paddles[1][1] < right_y),
+            state => panic!("expected a state, got {:?}", state),
+        }
+
+        server.receive(
+            1,
+            Message::Input {
+                sequence: 500,
+                input: PaddleInput::default(),
+            },
+        );
+        server.receive(1, Message::Disconnect);
+        server.tick();
+        assert!(
+            server.simulation().balls.is_empty(),
+            "match goes on without a player"
+        );
+        assert_eq!(connect(&mut server, 5, false), Some(Side::Left));
+
+        for _ in 0..SERVE_DELAY_TICKS {
+            server.tick();
+        }
+        // The new player's inputs count even though they start their sequence over.
+        let left_y = server.simulation().paddles[0].position[1];
+        server.receive(
+            5,
+            Message::Input {
+                sequence: 1,
+                input: PaddleInput { movement: 127 },
+            },
+        );
+        match server.tick() {
+            Message::State { snapshot, .. } => assert!(snapshot.paddles[0][1] > left_y),
+            state => panic!("expected a state, got {:?}", state),
+        }
+        server.receive(
+            5,
+            Message::Input {
+                sequence: 2,
+                input: PaddleInput::default(),
+            },
+        );
+
+        // Nobody moves, so the match ends after a while.
+        let mut ticks = 0;
+        while server.simulation().winner.is_none() {
+            for client in 2..=5 {
+                server.receive(client, Message::Connect { spectator: false });
+            }
+            server.tick();
+            ticks += 1;
+            assert!(ticks < 100_000, "match never ended");
+        }
+        for _ in 0..RESTART_DELAY_TICKS + SERVE_DELAY_TICKS {
+            server.tick();
+        }
+        assert_eq!(server.side_of(5), Some(Side::Left));
+        assert_eq!(server.side_of(3), Some(Side::Right));
+        assert_eq!(server.simulation().winner, None);
+        assert_eq!(server.simulation().balls.len(), 1, "no rematch");
+    }
+
+    #[test]
+    fn silent_clients_are_dropped() {
+        let mut server = MatchServer::new(MatchRules::default(), SpeedUp::default());
+        connect(&mut server, 1, false);
+        connect(&mut server, 2, true);
+        for _ in 0..TIMEOUT_TICKS {
+            server.tick();
+            server.receive(2, Message::Connect { spectator: true });
+        }
+        server.tick();
+        assert_eq!(server.clients().collect::<Vec<_>>(), vec![2]);
+    }
+}
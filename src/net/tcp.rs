@@ -0,0 +1,95 @@
+use crate::net::Transport;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bytes waiting to be sent before the other side counts as not keeping up.
+const MAX_BACKLOG: usize = 256 * 1024;
+
+/// Packets over a non-blocking TCP connection, each prefixed with its length. Nothing gets lost
+/// or reordered, but a slow reader holds everything up behind it.
+pub struct TcpTransport {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+impl TcpTransport {
+    pub fn connect(address: SocketAddr) -> io::Result<TcpTransport> {
+        TcpTransport::new(TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<TcpTransport> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
+impl Transport for TcpTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        if self.outgoing.len() > MAX_BACKLOG {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the other side does not keep up",
+            ));
+        }
+        self.outgoing
+            .extend_from_slice(&(packet.len() as u16).to_le_bytes());
+        self.outgoing.extend_from_slice(packet);
+        self.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.flush()?;
+        let mut buffer = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(length) => self.incoming.extend_from_slice(&buffer[..length]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.incoming.len() >= 2 {
+            let length = usize::from(u16::from_le_bytes([self.incoming[0], self.incoming[1]]));
+            if self.incoming.len() >= 2 + length {
+                let packet = self.incoming[2..2 + length].to_vec();
+                self.incoming.drain(..2 + length);
+                return Ok(Some(packet));
+            }
+        }
+        if self.closed {
+            Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "the connection was closed",
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::net::Transport;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Largest packet we expect, inputs packets stay far below it.
+const MAX_PACKET_SIZE: usize = 1500;
+
+/// Packets to and from one peer over a non-blocking UDP socket.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    /// Unknown to the hosting peer until the other one sent its first packet.
+    peer: Option<SocketAddr>,
+}
+impl UdpTransport {
+    /// Waits on `port` for the other peer, whoever sends first becomes the peer.
+    pub fn listen(port: u16) -> io::Result<UdpTransport> {
+        UdpTransport::new(UdpSocket::bind(("0.0.0.0", port))?, None)
+    }
+
+    pub fn connect(peer: SocketAddr) -> io::Result<UdpTransport> {
+        let socket = if peer.is_ipv4() {
+            UdpSocket::bind("0.0.0.0:0")?
+        } else {
+            UdpSocket::bind("[::]:0")?
+        };
+        UdpTransport::new(socket, Some(peer))
+    }
+
+    pub(super) fn new(socket: UdpSocket, peer: Option<SocketAddr>) -> io::Result<UdpTransport> {
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, peer })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        let peer = match self.peer {
+            Some(peer) => peer,
+            None => return Ok(()),
+        };
+        match self.socket.send_to(packet, peer) {
+            Ok(_) => Ok(()),
+            // A full send buffer is just another lost packet.
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => {
+                    let peer = *self.peer.get_or_insert(from);
+                    if from == peer {
+                        return Ok(Some(buffer[..length].to_vec()));
+                    }
+                }
+                Err(error) => {
+                    return match error.kind() {
+                        // The peer is not listening (yet), it may still start.
+                        io::ErrorKind::WouldBlock
+                        | io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionReset => Ok(None),
+                        _ => Err(error),
+                    };
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,661 @@
+use std::f32::consts::FRAC_PI_3;
+
+/// Upper bound of contacts resolved for a single ball in a single step. A ball that is still
+/// bouncing after that many contacts stops for the rest of the step instead of tunneling.
+const MAX_CONTACTS_PER_STEP: usize = 16;
+
+/// Steepest angle (against the horizontal) a ball can leave a paddle at.
+pub const MAX_BOUNCE_ANGLE: f32 = FRAC_PI_3;
+
+/// How much faster the ball gets with every paddle hit.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpeedUp {
+    pub factor: f32,
+    pub max_speed: f32,
+}
+impl Default for SpeedUp {
+    fn default() -> Self {
+        SpeedUp {
+            factor: 1.05,
+            max_speed: 180.0,
+        }
+    }
+}
+impl SpeedUp {
+    /// Scales the velocity by `factor` without exceeding `max_speed`. A ball that is already
+    /// faster than the cap keeps its speed.
+    pub fn apply(&self, velocity: [f32; 2]) -> [f32; 2] {
+        let speed = (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt();
+        if speed == 0.0 {
+            return velocity;
+        }
+
+        let target = (speed * self.factor).min(self.max_speed.max(speed));
+        let scale = target / speed;
+        [velocity[0] * scale, velocity[1] * scale]
+    }
+}
+
+/// Returns the velocity of a ball bouncing off a paddle.
+///
+/// `offset` is where the ball hit the paddle: `0.0` is the center, `1.0` the top edge and `-1.0`
+/// the bottom edge, values beyond are clamped. The ball leaves in the opposite horizontal
+/// direction at an angle proportional to the offset, its speed is preserved.
+pub fn deflect(velocity: [f32; 2], offset: f32, max_angle: f32) -> [f32; 2] {
+    let speed = (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt();
+    let angle = offset.clamp(-1.0, 1.0) * max_angle;
+    let direction = if velocity[0] > 0.0 { -1.0 } else { 1.0 };
+
+    [direction * speed * angle.cos(), speed * angle.sin()]
+}
+
+/// Where the borders of the arena stop the ball, `None` for borders the ball may leave through.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Walls {
+    pub left: Option<f32>,
+    pub right: Option<f32>,
+    pub bottom: Option<f32>,
+    pub top: Option<f32>,
+}
+impl Walls {
+    /// The lower and upper wall along an axis, `0` for x and `1` for y.
+    fn along(&self, axis: usize) -> (Option<f32>, Option<f32>) {
+        if axis == 0 {
+            (self.left, self.right)
+        } else {
+            (self.bottom, self.top)
+        }
+    }
+}
+
+/// Axis aligned rectangle, e.g. a paddle.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+impl Aabb {
+    pub fn from_center(center: [f32; 2], width: f32, height: f32) -> Aabb {
+        Aabb {
+            min: [center[0] - width * 0.5, center[1] - height * 0.5],
+            max: [center[0] + width * 0.5, center[1] + height * 0.5],
+        }
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            (self.min[0] + self.max[0]) * 0.5,
+            (self.min[1] + self.max[1]) * 0.5,
+        ]
+    }
+
+    /// The axis the rectangle is thinnest along, `0` for an upright paddle and `1` for a flat
+    /// one. Its faces point along this axis.
+    pub fn thin_axis(&self) -> usize {
+        if self.max[0] - self.min[0] <= self.max[1] - self.min[1] {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Squared distance between `point` and the closest point of the rectangle.
+    pub fn distance_squared(&self, point: [f32; 2]) -> f32 {
+        let dx = (self.min[0] - point[0])
+            .max(point[0] - self.max[0])
+            .max(0.0);
+        let dy = (self.min[1] - point[1])
+            .max(point[1] - self.max[1])
+            .max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+/// First contact of a swept circle. `time` is the fraction of the displacement travelled before
+/// the contact, `normal` points away from the obstacle.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub time: f32,
+    pub normal: [f32; 2],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Collision {
+    Wall,
+    /// Index into the paddles passed to `move_ball`.
+    Paddle(usize),
+}
+
+/// A bounce of the ball during `move_ball`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Contact {
+    pub collision: Collision,
+    /// Where the ball center was when it touched.
+    pub position: [f32; 2],
+    /// The velocity the ball left with.
+    pub velocity: [f32; 2],
+}
+
+/// Sweeps a circle from `start` along `displacement` and returns the first contact with `aabb`.
+///
+/// The circle touches the rectangle exactly when its center touches the rectangle grown by
+/// `radius` with rounded corners. That shape is the union of the rectangle grown horizontally,
+/// the rectangle grown vertically and a circle around every corner, so the earliest hit of the
+/// center against any of those is the contact. Circles that already overlap the rectangle or
+/// move away from it do not hit.
+pub fn sweep_circle_aabb(
+    start: [f32; 2],
+    displacement: [f32; 2],
+    radius: f32,
+    aabb: &Aabb,
+) -> Option<Hit> {
+    let wide = Aabb {
+        min: [aabb.min[0] - radius, aabb.min[1]],
+        max: [aabb.max[0] + radius, aabb.max[1]],
+    };
+    let tall = Aabb {
+        min: [aabb.min[0], aabb.min[1] - radius],
+        max: [aabb.max[0], aabb.max[1] + radius],
+    };
+    let corners = [
+        [aabb.min[0], aabb.min[1]],
+        [aabb.min[0], aabb.max[1]],
+        [aabb.max[0], aabb.min[1]],
+        [aabb.max[0], aabb.max[1]],
+    ];
+
+    let hits = [
+        ray_aabb(start, displacement, &wide),
+        ray_aabb(start, displacement, &tall),
+    ];
+    let corner_hits = corners
+        .iter()
+        .map(|corner| ray_circle(start, displacement, *corner, radius));
+
+    hits.iter()
+        .copied()
+        .chain(corner_hits)
+        .flatten()
+        .filter(|hit| hit.normal[0] * displacement[0] + hit.normal[1] * displacement[1] < 0.0)
+        .min_by(|a, b| a.time.partial_cmp(&b.time).unwrap())
+}
+
+// Slab test of a ray against a rectangle, only entering hits within the ray count.
+fn ray_aabb(start: [f32; 2], displacement: [f32; 2], aabb: &Aabb) -> Option<Hit> {
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = [0.0, 0.0];
+
+    for axis in 0..2 {
+        if displacement[axis] == 0.0 {
+            if start[axis] < aabb.min[axis] || start[axis] > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let inverse = 1.0 / displacement[axis];
+        let mut near = (aabb.min[axis] - start[axis]) * inverse;
+        let mut far = (aabb.max[axis] - start[axis]) * inverse;
+        let mut side = -1.0;
+        if near > far {
+            std::mem::swap(&mut near, &mut far);
+            side = 1.0;
+        }
+
+        if near > entry {
+            entry = near;
+            normal = [0.0, 0.0];
+            normal[axis] = side;
+        }
+        exit = exit.min(far);
+    }
+
+    if entry > exit || !(0.0..=1.0).contains(&entry) {
+        None
+    } else {
+        Some(Hit {
+            time: entry,
+            normal,
+        })
+    }
+}
+
+fn ray_circle(
+    start: [f32; 2],
+    displacement: [f32; 2],
+    center: [f32; 2],
+    radius: f32,
+) -> Option<Hit> {
+    let offset = [start[0] - center[0], start[1] - center[1]];
+    let a = displacement[0] * displacement[0] + displacement[1] * displacement[1];
+    let b = 2.0 * (offset[0] * displacement[0] + offset[1] * displacement[1]);
+    let c = offset[0] * offset[0] + offset[1] * offset[1] - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || c < 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+
+    let contact = [
+        start[0] + displacement[0] * time,
+        start[1] + displacement[1] * time,
+    ];
+    Some(Hit {
+        time,
+        normal: [
+            (contact[0] - center[0]) / radius,
+            (contact[1] - center[1]) / radius,
+        ],
+    })
+}
+
+/// Moves a ball for `delta` seconds, bouncing off walls and paddles along the way. Contacts are
+/// found with swept tests so the ball can't skip over a paddle no matter how fast it is.
+///
+/// Hits on the front or back of a paddle, including corner hits that come more from the front
+/// than from the ends, are handed to `on_paddle_hit` with the paddle index, the ball position
+/// and velocity at the contact, it returns the velocity after the bounce. `None` lets the ball
+/// pass through that paddle for the rest of the step. All other paddle hits reflect the ball
+/// along the contact normal. Returns all contacts in order, passed paddles are no contacts.
+pub fn move_ball<F>(
+    position: &mut [f32; 2],
+    velocity: &mut [f32; 2],
+    radius: f32,
+    delta: f32,
+    walls: &Walls,
+    paddles: &[Aabb],
+    mut on_paddle_hit: F,
+) -> Vec<Contact>
+where
+    F: FnMut(usize, [f32; 2], [f32; 2]) -> Option<[f32; 2]>,
+{
+    let mut contacts = Vec::new();
+    let mut passed = vec![false; paddles.len()];
+
+    // A paddle moving into a resting ball is not caught by the sweep, push the ball out to the
+    // side it is on if it is heading into the paddle.
+    for (index, paddle) in paddles.iter().enumerate() {
+        let center = paddle.center();
+        let axis = paddle.thin_axis();
+        let towards_paddle = (center[axis] - position[axis]) * velocity[axis] > 0.0;
+        if towards_paddle && paddle.distance_squared(*position) < radius * radius {
+            match on_paddle_hit(index, *position, *velocity) {
+                Some(bounced) => {
+                    *velocity = bounced;
+                    contacts.push(Contact {
+                        collision: Collision::Paddle(index),
+                        position: *position,
+                        velocity: bounced,
+                    });
+                }
+                None => passed[index] = true,
+            }
+        }
+    }
+
+    let mut remaining = delta;
+    while remaining > 0.0 {
+        if contacts.len() >= MAX_CONTACTS_PER_STEP {
+            break;
+        }
+
+        let displacement = [velocity[0] * remaining, velocity[1] * remaining];
+
+        let mut first: Option<(Hit, Collision)> = None;
+        let mut consider = |hit: Hit, collision: Collision| match first {
+            Some((other, _)) if other.time <= hit.time => {}
+            _ => first = Some((hit, collision)),
+        };
+
+        // The walls stop the ball center one radius away from them.
+        for axis in 0..2 {
+            let (lower, upper) = walls.along(axis);
+            let mut normal = [0.0, 0.0];
+            let limit = match (lower, upper) {
+                (Some(wall), _) if displacement[axis] < 0.0 => {
+                    normal[axis] = 1.0;
+                    wall + radius
+                }
+                (_, Some(wall)) if displacement[axis] > 0.0 => {
+                    normal[axis] = -1.0;
+                    wall - radius
+                }
+                _ => continue,
+            };
+            if (position[axis] + displacement[axis] - limit) * normal[axis] < 0.0 {
+                let time = ((limit - position[axis]) / displacement[axis]).max(0.0);
+                consider(Hit { time, normal }, Collision::Wall);
+            }
+        }
+        for (index, paddle) in paddles
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !passed[*index])
+        {
+            if let Some(hit) = sweep_circle_aabb(*position, displacement, radius, paddle) {
+                consider(hit, Collision::Paddle(index));
+            }
+        }
+
+        let (hit, collision) = match first {
+            Some(first) => first,
+            None => {
+                position[0] += displacement[0];
+                position[1] += displacement[1];
+                break;
+            }
+        };
+
+        position[0] += displacement[0] * hit.time;
+        position[1] += displacement[1] * hit.time;
+        remaining *= 1.0 - hit.time;
+
+        *velocity = match collision {
+            Collision::Paddle(index) if is_face_hit(&paddles[index], hit.normal) => {
+                match on_paddle_hit(index, *position, *velocity) {
+                    Some(bounced) => bounced,
+                    None => {
+                        passed[index] = true;
+                        continue;
+                    }
+                }
+            }
+            _ => reflect(*velocity, hit.normal),
+        };
+        contacts.push(Contact {
+            collision,
+            position: *position,
+            velocity: *velocity,
+        });
+    }
+
+    contacts
+}
+
+fn is_face_hit(paddle: &Aabb, normal: [f32; 2]) -> bool {
+    let axis = paddle.thin_axis();
+    normal[axis].abs() >= normal[1 - axis].abs()
+}
+
+fn reflect(velocity: [f32; 2], normal: [f32; 2]) -> [f32; 2] {
+    let dot = velocity[0] * normal[0] + velocity[1] * normal[1];
+    [
+        velocity[0] - 2.0 * dot * normal[0],
+        velocity[1] - 2.0 * dot * normal[1],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Rng;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn speed(velocity: [f32; 2]) -> f32 {
+        (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt()
+    }
+
+    #[test]
+    fn center_hit_returns_horizontally() {
+        let velocity = deflect([75.0, 50.0], 0.0, MAX_BOUNCE_ANGLE);
+        assert!(velocity[0] < 0.0);
+        assert!(velocity[1].abs() < EPSILON);
+    }
+
+    #[test]
+    fn edge_hit_uses_max_angle() {
+        let velocity = deflect([-75.0, 0.0], 1.0, MAX_BOUNCE_ANGLE);
+        let angle = velocity[1].atan2(velocity[0]);
+        assert!(velocity[0] > 0.0);
+        assert!((angle - MAX_BOUNCE_ANGLE).abs() < EPSILON);
+
+        let velocity = deflect([-75.0, 0.0], -1.0, MAX_BOUNCE_ANGLE);
+        let angle = velocity[1].atan2(velocity[0]);
+        assert!((angle + MAX_BOUNCE_ANGLE).abs() < EPSILON);
+    }
+
+    #[test]
+    fn offset_is_clamped() {
+        let edge = deflect([75.0, 50.0], 1.0, MAX_BOUNCE_ANGLE);
+        let beyond = deflect([75.0, 50.0], 3.0, MAX_BOUNCE_ANGLE);
+        assert!((edge[0] - beyond[0]).abs() < EPSILON);
+        assert!((edge[1] - beyond[1]).abs() < EPSILON);
+    }
+
+    #[test]
+    fn speed_up_stops_at_the_max_speed() {
+        let speed_up = SpeedUp {
+            factor: 1.5,
+            max_speed: 200.0,
+        };
+        let faster = speed_up.apply([60.0, 80.0]);
+        assert!((speed(faster) - 150.0).abs() < EPSILON);
+        assert!((faster[1] / faster[0] - 80.0 / 60.0).abs() < EPSILON);
+
+        assert!((speed(speed_up.apply([150.0, 0.0])) - 200.0).abs() < EPSILON);
+        assert_eq!(speed_up.apply([0.0, 250.0]), [0.0, 250.0]);
+        assert_eq!(speed_up.apply([0.0, 0.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn speed_is_preserved() {
+        for step in -10..=10 {
+            let offset = step as f32 / 10.0;
+            let velocity = [75.0, 50.0];
+            let deflected = deflect(velocity, offset, MAX_BOUNCE_ANGLE);
+            assert!((speed(velocity) - speed(deflected)).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn angle_grows_with_offset() {
+        let mut last_angle = -MAX_BOUNCE_ANGLE - EPSILON;
+        for step in -10..=10 {
+            let velocity = deflect([-75.0, 20.0], step as f32 / 10.0, MAX_BOUNCE_ANGLE);
+            let angle = velocity[1].atan2(velocity[0]);
+            assert!(angle > last_angle);
+            last_angle = angle;
+        }
+    }
+
+    const ARENA: f32 = 100.0;
+    const RADIUS: f32 = 2.0;
+
+    fn paddle() -> Aabb {
+        Aabb::from_center([98.0, 50.0], 4.0, 16.0)
+    }
+
+    // The two-player arena, open to the left and right.
+    fn walls() -> Walls {
+        Walls {
+            left: None,
+            right: None,
+            bottom: Some(0.0),
+            top: Some(ARENA),
+        }
+    }
+
+    // Bounce straight back, like a paddle without deflection.
+    fn mirror(_: usize, _: [f32; 2], velocity: [f32; 2]) -> Option<[f32; 2]> {
+        Some([-velocity[0], velocity[1]])
+    }
+
+    #[test]
+    fn fast_balls_do_not_tunnel_through_paddles() {
+        let mut rng = Rng::new(7);
+        let paddle = paddle();
+
+        for _ in 0..10_000 {
+            // Aim at a random point of the paddle's front face from a random spot left of it.
+            let start = [rng.range(5.0, 90.0), rng.range(10.0, 90.0)];
+            let target = [
+                paddle.min[0] - RADIUS,
+                rng.range(paddle.min[1], paddle.max[1]),
+            ];
+            let speed = rng.range(50.0, 100_000.0);
+            let direction = [target[0] - start[0], target[1] - start[1]];
+            let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+            let mut velocity = [direction[0] / length * speed, direction[1] / length * speed];
+
+            // Always long enough to end up well behind the paddle without a collision.
+            let delta = (length + 50.0) / speed * rng.range(1.0, 4.0);
+            let mut position = start;
+            let contacts = move_ball(
+                &mut position,
+                &mut velocity,
+                RADIUS,
+                delta,
+                &walls(),
+                &[paddle],
+                mirror,
+            );
+
+            assert!(
+                contacts
+                    .iter()
+                    .any(|contact| contact.collision == Collision::Paddle(0)),
+                "ball from {:?} at speed {} missed the paddle",
+                start,
+                speed
+            );
+            assert!(position[0] <= paddle.min[0] - RADIUS + 1e-2);
+        }
+    }
+
+    #[test]
+    fn balls_never_end_inside_a_paddle() {
+        let mut rng = Rng::new(11);
+        let paddle = paddle();
+
+        for _ in 0..10_000 {
+            let mut position = [rng.range(0.0, ARENA), rng.range(RADIUS, ARENA - RADIUS)];
+            if paddle.distance_squared(position) < RADIUS * RADIUS {
+                continue;
+            }
+
+            let angle = rng.range(0.0, std::f32::consts::PI * 2.0);
+            let speed = rng.range(10.0, 5_000.0);
+            let mut velocity = [angle.cos() * speed, angle.sin() * speed];
+            move_ball(
+                &mut position,
+                &mut velocity,
+                RADIUS,
+                rng.range(0.001, 0.1),
+                &walls(),
+                &[paddle],
+                |_, _, velocity| Some(deflect(velocity, 0.0, MAX_BOUNCE_ANGLE)),
+            );
+
+            assert!(
+                paddle.distance_squared(position) >= RADIUS * RADIUS - 1e-2,
+                "ball ended at {:?} inside the paddle",
+                position
+            );
+            assert!(position[1] >= RADIUS - 1e-3 && position[1] <= ARENA - RADIUS + 1e-3);
+        }
+    }
+
+    #[test]
+    fn corner_hit_reflects_along_the_normal() {
+        let paddle = paddle();
+        // Head straight at the bottom left corner, more from below than from the side.
+        let angle = 245.0f32.to_radians();
+        let normal = [angle.cos(), angle.sin()];
+        let contact = [
+            paddle.min[0] + normal[0] * RADIUS,
+            paddle.min[1] + normal[1] * RADIUS,
+        ];
+        let mut position = [contact[0] + normal[0] * 10.0, contact[1] + normal[1] * 10.0];
+        let mut velocity = [-normal[0] * 100.0, -normal[1] * 100.0];
+
+        let contacts = move_ball(
+            &mut position,
+            &mut velocity,
+            RADIUS,
+            0.15,
+            &walls(),
+            &[paddle],
+            |_, _, _| panic!("not a hit on the face"),
+        );
+
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].collision, Collision::Paddle(0));
+        assert!((contacts[0].position[0] - contact[0]).abs() < 1e-3);
+        assert!((contacts[0].position[1] - contact[1]).abs() < 1e-3);
+        assert!((velocity[0] - normal[0] * 100.0).abs() < 1e-2);
+        assert!((velocity[1] - normal[1] * 100.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn top_of_paddle_reflects_vertically() {
+        let paddle = paddle();
+        let mut position = [paddle.center()[0], paddle.max[1] + 10.0];
+        let mut velocity = [0.0, -100.0];
+
+        move_ball(
+            &mut position,
+            &mut velocity,
+            RADIUS,
+            0.1,
+            &walls(),
+            &[paddle],
+            |_, _, _| panic!("not a hit on the face"),
+        );
+
+        assert!(velocity[1] > 0.0);
+        assert!(position[1] >= paddle.max[1] + RADIUS - 1e-3);
+    }
+
+    #[test]
+    fn flat_paddles_take_hits_from_above_on_their_face() {
+        let paddle = Aabb::from_center([50.0, 98.0], 16.0, 4.0);
+        let mut position = [52.0, 80.0];
+        let mut velocity = [0.0, 100.0];
+
+        let contacts = move_ball(
+            &mut position,
+            &mut velocity,
+            RADIUS,
+            0.2,
+            &Walls {
+                top: None,
+                ..walls()
+            },
+            &[paddle],
+            |_, _, velocity| Some([velocity[0], -velocity[1]]),
+        );
+
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].collision, Collision::Paddle(0));
+        assert!(velocity[1] < 0.0);
+        assert!(position[1] <= paddle.min[1] - RADIUS + 1e-3);
+    }
+
+    #[test]
+    fn balls_pass_through_paddles_that_let_them() {
+        let paddle = Aabb::from_center([30.0, 50.0], 4.0, 16.0);
+        let mut position = [20.0, 50.0];
+        let mut velocity = [100.0, 0.0];
+
+        let contacts = move_ball(
+            &mut position,
+            &mut velocity,
+            RADIUS,
+            0.2,
+            &walls(),
+            &[paddle],
+            |_, _, _| None,
+        );
+
+        assert!(contacts.is_empty());
+        assert_eq!(velocity, [100.0, 0.0]);
+        assert!((position[0] - 40.0).abs() < 1e-3);
+    }
+}
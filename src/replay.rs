@@ -0,0 +1,444 @@
+use crate::physics::SpeedUp;
+use crate::rules::{MatchMode, MatchRules};
+use crate::simulation::{PaddleInput, SimBall, Simulation, BALL_RADIUS, MAX_PLAYERS};
+use crate::utils::{ByteReader, Truncated};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"PONGRPLY";
+/// Bumped whenever the file layout or the simulation changes in a way that makes old replays
+/// play out differently.
+pub const REPLAY_VERSION: u16 = 2;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// Not a replay file at all.
+    BadMagic,
+    /// Recorded by a different version of the game, it would desync.
+    UnsupportedVersion(u16),
+    /// The file ends in the middle of the data.
+    Truncated,
+    Invalid(String),
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "{}", error),
+            ReplayError::BadMagic => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {} is not supported, this game plays version {}",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Truncated => write!(f, "replay file is truncated"),
+            ReplayError::Invalid(message) => write!(f, "invalid replay: {}", message),
+        }
+    }
+}
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+impl From<Truncated> for ReplayError {
+    fn from(_: Truncated) -> Self {
+        ReplayError::Truncated
+    }
+}
+
+/// Where everything was after a tick, enough to show the match at that point.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    pub tick: u64,
+    /// Indexed by player.
+    pub paddles: Vec<[f32; 2]>,
+    pub balls: Vec<[f32; 2]>,
+    pub score_left: i32,
+    pub score_right: i32,
+    /// Lives left in a four-player match, indexed by `Side::index`.
+    pub lives: Vec<i32>,
+}
+impl Snapshot {
+    pub fn capture(simulation: &Simulation) -> Snapshot {
+        Snapshot {
+            tick: simulation.tick,
+            paddles: simulation
+                .paddles
+                .iter()
+                .map(|paddle| paddle.position)
+                .collect(),
+            balls: simulation.balls.iter().map(|ball| ball.position).collect(),
+            score_left: simulation.score_left,
+            score_right: simulation.score_right,
+            lives: simulation.lives.clone(),
+        }
+    }
+
+    /// Puts this snapshot and the `next` one into `simulation` as previous and current positions,
+    /// so the interpolation between two ticks draws whatever lies in between them.
+    pub fn show_until(&self, next: &Snapshot, simulation: &mut Simulation) {
+        simulation.tick = self.tick;
+        simulation.score_left = self.score_left;
+        simulation.score_right = self.score_right;
+        simulation.lives = self.lives.clone();
+        simulation.winner = None;
+        // Snapshots come off the network too, paddles they lack stay where they are.
+        for (index, paddle) in simulation.paddles.iter_mut().enumerate() {
+            if let Some(previous_position) = self.paddles.get(index) {
+                paddle.previous_position = *previous_position;
+                paddle.position = next
+                    .paddles
+                    .get(index)
+                    .copied()
+                    .unwrap_or(*previous_position);
+            }
+        }
+        simulation.balls = self
+            .balls
+            .iter()
+            .enumerate()
+            .map(|(index, previous_position)| SimBall {
+                previous_position: *previous_position,
+                position: next.balls.get(index).copied().unwrap_or(*previous_position),
+                velocity: [0.0, 0.0],
+                radius: BALL_RADIUS,
+            })
+            .collect();
+    }
+}
+
+/// Everything needed to play a match again tick by tick: the settings it started with and the
+/// inputs of all paddles on every tick.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// Seed of the computer players' random numbers. Their decisions are part of the inputs,
+    /// the seed is kept to tell matches apart and to rerun the computer players themselves.
+    pub seed: u64,
+    pub rules: MatchRules,
+    pub speed_up: SpeedUp,
+    /// Indexed by tick, then by player. Inputs of paddles that are not in the match are
+    /// ignored and not saved.
+    pub inputs: Vec<[PaddleInput; MAX_PLAYERS]>,
+}
+impl Replay {
+    pub fn new(seed: u64, rules: MatchRules, speed_up: SpeedUp) -> Replay {
+        Replay {
+            seed,
+            rules,
+            speed_up,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// A fresh simulation with the recorded settings, ready to be served.
+    pub fn simulation(&self) -> Simulation {
+        Simulation::new(self.rules.clone(), self.speed_up.clone())
+    }
+
+    /// Serves a new simulation and runs it through all recorded ticks.
+    pub fn play(&self) -> Simulation {
+        let mut simulation = self.simulation();
+        simulation.serve();
+        for inputs in &self.inputs {
+            simulation.step(inputs);
+        }
+        simulation
+    }
+
+    /// Simulates the whole match and keeps the state after every tick, indexed by tick. The
+    /// first snapshot shows the serve.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        let mut simulation = self.simulation();
+        simulation.serve();
+        let mut snapshots = vec![Snapshot::capture(&simulation)];
+        for inputs in &self.inputs {
+            simulation.step(inputs);
+            snapshots.push(Snapshot::capture(&simulation));
+        }
+        snapshots
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        Replay::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Little endian throughout. Inputs are stored as runs of identical ticks, paddles rarely
+    /// change direction every tick.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&REPLAY_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+
+        write_settings(&mut bytes, &self.rules, &self.speed_up);
+
+        let players = self.rules.players();
+        let mut runs: Vec<(u16, &[PaddleInput])> = Vec::new();
+        for inputs in &self.inputs {
+            match runs.last_mut() {
+                Some((length, run_inputs))
+                    if *run_inputs == &inputs[..players] && *length < u16::MAX =>
+                {
+                    *length += 1
+                }
+                _ => runs.push((1, &inputs[..players])),
+            }
+        }
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (length, inputs) in runs {
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.extend(inputs.iter().map(|input| input.movement as u8));
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(ReplayError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let seed = reader.u64()?;
+
+        let (rules, speed_up) = read_settings(&mut reader)?;
+
+        let mut inputs = Vec::new();
+        for _ in 0..reader.u32()? {
+            let length = reader.u16()?;
+            let mut run_inputs = [PaddleInput::default(); MAX_PLAYERS];
+            for input in &mut run_inputs[..rules.players()] {
+                input.movement = reader.u8()? as i8;
+            }
+            inputs.extend((0..length).map(|_| run_inputs));
+        }
+        if !reader.remaining().is_empty() {
+            return Err(ReplayError::Invalid(format!(
+                "{} unexpected bytes at the end",
+                reader.remaining().len()
+            )));
+        }
+
+        Ok(Replay {
+            seed,
+            rules,
+            speed_up,
+            inputs,
+        })
+    }
+}
+
+/// The rules and the ball speed up, written the same way into replays and network packets.
+pub(crate) fn write_settings(bytes: &mut Vec<u8>, rules: &MatchRules, speed_up: &SpeedUp) {
+    bytes.extend_from_slice(&rules.points_to_win.to_le_bytes());
+    bytes.push(rules.win_by_two as u8);
+    match rules.time_limit {
+        Some(limit) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&limit.to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.push(match rules.mode {
+        MatchMode::Singles => 0,
+        MatchMode::FourPlayers => 1,
+        MatchMode::Doubles => 2,
+    });
+    bytes.extend_from_slice(&rules.lives.to_le_bytes());
+    bytes.extend_from_slice(&speed_up.factor.to_le_bytes());
+    bytes.extend_from_slice(&speed_up.max_speed.to_le_bytes());
+}
+
+pub(crate) fn read_settings(
+    reader: &mut ByteReader<'_>,
+) -> Result<(MatchRules, SpeedUp), ReplayError> {
+    let points_to_win = reader.i32()?;
+    let win_by_two = reader.u8()? != 0;
+    let time_limit = match reader.u8()? {
+        0 => None,
+        _ => Some(reader.f32()?),
+    };
+    let mode = match reader.u8()? {
+        0 => MatchMode::Singles,
+        1 => MatchMode::FourPlayers,
+        2 => MatchMode::Doubles,
+        code => return Err(ReplayError::Invalid(format!("match mode {}", code))),
+    };
+    let lives = reader.i32()?;
+    if points_to_win <= 0 {
+        return Err(ReplayError::Invalid(format!(
+            "{} points to win",
+            points_to_win
+        )));
+    }
+    if lives <= 0 {
+        return Err(ReplayError::Invalid(format!("{} lives", lives)));
+    }
+    // The same bounds as on the command line, see `parse_setting`.
+    if let Some(seconds) = time_limit {
+        if seconds.is_nan() || seconds <= 0.0 {
+            return Err(ReplayError::Invalid(format!("time limit {}", seconds)));
+        }
+    }
+    let speed_up = SpeedUp {
+        factor: reader.f32()?,
+        max_speed: reader.f32()?,
+    };
+    if speed_up.factor.is_nan() || speed_up.factor < 1.0 {
+        return Err(ReplayError::Invalid(format!(
+            "speed up factor {}",
+            speed_up.factor
+        )));
+    }
+    if speed_up.max_speed.is_nan() || speed_up.max_speed <= 0.0 {
+        return Err(ReplayError::Invalid(format!(
+            "max ball speed {}",
+            speed_up.max_speed
+        )));
+    }
+    let rules = MatchRules {
+        points_to_win,
+        win_by_two,
+        time_limit,
+        mode,
+        lives,
+    };
+    Ok((rules, speed_up))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AiController, AiDifficulty, AiPresets};
+    use crate::utils::Rng;
+
+    fn record_match(seed: u64) -> (Replay, Simulation) {
+        let presets = AiPresets::default();
+        let rules = MatchRules {
+            points_to_win: 5,
+            ..MatchRules::default()
+        };
+        let mut replay = Replay::new(seed, rules, SpeedUp::default());
+        let mut left = AiController::new(presets.get(AiDifficulty::Hard).clone(), Rng::new(seed));
+        let mut right = AiController::new(presets.get(AiDifficulty::Easy).clone(), Rng::new(!seed));
+
+        let mut simulation = replay.simulation();
+        simulation.serve();
+        while simulation.winner.is_none() {
+            let mut inputs = [PaddleInput::default(); MAX_PLAYERS];
+            inputs[0] = left.input(&simulation, 0);
+            inputs[1] = right.input(&simulation, 1);
+            replay.inputs.push(inputs);
+            simulation.step(&inputs);
+        }
+        (replay, simulation)
+    }
+
+    #[test]
+    fn replays_end_with_the_recorded_score() {
+        let (replay, recorded) = record_match(7);
+        let loaded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(loaded.inputs, replay.inputs);
+
+        let replayed = loaded.play();
+        assert_eq!(replayed.score_left, recorded.score_left);
+        assert_eq!(replayed.score_right, recorded.score_right);
+        assert_eq!(replayed.winner, recorded.winner);
+        assert_eq!(replayed.state_hash(), recorded.state_hash());
+    }
+
+    #[test]
+    fn snapshots_follow_the_match() {
+        let (replay, recorded) = record_match(11);
+        let snapshots = replay.snapshots();
+
+        assert_eq!(snapshots.len(), replay.inputs.len() + 1);
+        assert_eq!((snapshots[0].score_left, snapshots[0].score_right), (0, 0));
+        let last = snapshots.last().unwrap();
+        assert_eq!(last.tick, recorded.tick);
+        assert_eq!(
+            (last.score_left, last.score_right),
+            (recorded.score_left, recorded.score_right)
+        );
+    }
+
+    #[test]
+    fn snapshots_without_every_paddle_leave_the_rest() {
+        let mut simulation = Simulation::new(MatchRules::default(), SpeedUp::default());
+        let right = simulation.paddles[1].position;
+        let mut snapshot = Snapshot::capture(&simulation);
+        snapshot.paddles = vec![[2.0, 20.0]];
+        let next = Snapshot {
+            paddles: Vec::new(),
+            ..snapshot.clone()
+        };
+
+        snapshot.show_until(&next, &mut simulation);
+        assert_eq!(simulation.paddles[0].position, [2.0, 20.0]);
+        assert_eq!(simulation.paddles[1].position, right);
+    }
+
+    #[test]
+    fn other_versions_and_broken_files_are_rejected() {
+        let (replay, _) = record_match(3);
+        let bytes = replay.to_bytes();
+
+        let mut old = bytes.clone();
+        old[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(
+            Replay::from_bytes(&old),
+            Err(ReplayError::UnsupportedVersion(0))
+        ));
+        assert!(matches!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReplayError::Truncated)
+        ));
+        assert!(matches!(
+            Replay::from_bytes(b"PNG not a replay"),
+            Err(ReplayError::BadMagic)
+        ));
+
+        let broken_settings = [
+            (Some(-5.0), SpeedUp::default()),
+            (Some(f32::NAN), SpeedUp::default()),
+            (
+                None,
+                SpeedUp {
+                    factor: 0.5,
+                    ..SpeedUp::default()
+                },
+            ),
+            (
+                None,
+                SpeedUp {
+                    max_speed: f32::NAN,
+                    ..SpeedUp::default()
+                },
+            ),
+        ];
+        for (time_limit, speed_up) in broken_settings.iter() {
+            let rules = MatchRules {
+                time_limit: *time_limit,
+                ..MatchRules::default()
+            };
+            let bytes = Replay::new(1, rules, speed_up.clone()).to_bytes();
+            assert!(matches!(
+                Replay::from_bytes(&bytes),
+                Err(ReplayError::Invalid(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::physics::SpeedUp;
+use crate::simulation::Side;
+
+/// Who plays on which side.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchMode {
+    /// One paddle on the left against one on the right.
+    Singles,
+    /// Two teams of two, each player has their own lane on their team's half.
+    Doubles,
+    /// Paddles on all four sides, see `MatchRules::lives`.
+    FourPlayers,
+}
+impl MatchMode {
+    /// The side and lane of every player's paddle, indexed by player. Lane `0` is on the goal
+    /// line, further lanes are closer to the center. The first two players are always the left
+    /// and the right back paddle, so `Side::index` finds them.
+    pub fn layout(self) -> &'static [(Side, usize)] {
+        match self {
+            MatchMode::Singles => &[(Side::Left, 0), (Side::Right, 0)],
+            MatchMode::Doubles => &[
+                (Side::Left, 0),
+                (Side::Right, 0),
+                (Side::Left, 1),
+                (Side::Right, 1),
+            ],
+            MatchMode::FourPlayers => &[
+                (Side::Left, 0),
+                (Side::Right, 0),
+                (Side::Top, 0),
+                (Side::Bottom, 0),
+            ],
+        }
+    }
+}
+
+/// How a match is won, checked by the `Simulation` after every tick.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchRules {
+    pub points_to_win: i32,
+    /// The winner needs a lead of at least two points.
+    pub win_by_two: bool,
+    /// Seconds of play after which the leader wins. A tie at that point goes into sudden
+    /// death: the next point wins.
+    pub time_limit: Option<f32>,
+    pub mode: MatchMode,
+    /// Lives of every player in a four-player match. Instead of scoring points a player loses
+    /// one whenever the ball gets into their goal, the last player left wins.
+    pub lives: i32,
+}
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            points_to_win: 10,
+            win_by_two: false,
+            time_limit: None,
+            mode: MatchMode::Singles,
+            lives: 3,
+        }
+    }
+}
+impl MatchRules {
+    /// Number of paddles in the match.
+    pub fn players(&self) -> usize {
+        self.mode.layout().len()
+    }
+
+    pub fn winner(&self, score_left: i32, score_right: i32, elapsed: f32) -> Option<Side> {
+        let (leader, lead, leader_score) = if score_left > score_right {
+            (Side::Left, score_left - score_right, score_left)
+        } else if score_right > score_left {
+            (Side::Right, score_right - score_left, score_right)
+        } else {
+            return None;
+        };
+
+        let reached_points = leader_score >= self.points_to_win && (!self.win_by_two || lead >= 2);
+        if reached_points || self.is_time_up(elapsed) {
+            Some(leader)
+        } else {
+            None
+        }
+    }
+
+    /// True if `side` wins the match by scoring the next point.
+    pub fn is_match_point(
+        &self,
+        side: Side,
+        score_left: i32,
+        score_right: i32,
+        elapsed: f32,
+    ) -> bool {
+        let (score_left, score_right) = match side {
+            Side::Left => (score_left + 1, score_right),
+            Side::Right => (score_left, score_right + 1),
+            Side::Top | Side::Bottom => return false,
+        };
+        self.winner(score_left, score_right, elapsed) == Some(side)
+    }
+
+    /// Winner of a four-player match with the given lives, indexed by `Side::index`: the only
+    /// player with lives left, or the one with the most lives once time is up. A tie at that
+    /// point goes into sudden death until a single player leads.
+    pub fn last_standing(&self, lives: &[i32], elapsed: f32) -> Option<Side> {
+        let most = *lives.iter().max()?;
+        let mut leaders = Side::ALL
+            .iter()
+            .zip(lives)
+            .filter(|(_, lives)| **lives == most);
+        let (leader, _) = leaders.next()?;
+        if leaders.next().is_some() {
+            return None;
+        }
+
+        let standing = lives.iter().filter(|lives| **lives > 0).count();
+        if standing <= 1 || self.is_time_up(elapsed) {
+            Some(*leader)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_time_up(&self, elapsed: f32) -> bool {
+        matches!(self.time_limit, Some(limit) if elapsed >= limit)
+    }
+
+    pub fn remaining_time(&self, elapsed: f32) -> Option<f32> {
+        self.time_limit.map(|limit| (limit - elapsed).max(0.0))
+    }
+}
+
+/// Applies one of the match settings both the game and the server take on the command line:
+/// `--points=N`, `--win-by-two`, `--time-limit=SECONDS`, `--speed-up=FACTOR` and
+/// `--max-ball-speed=SPEED`. `None` if `name` is none of them.
+pub fn parse_setting(
+    rules: &mut MatchRules,
+    speed_up: &mut SpeedUp,
+    name: &str,
+    value: Option<&str>,
+) -> Option<Result<(), String>> {
+    let number = value.and_then(|value| value.parse::<f32>().ok());
+    let invalid = |what: &str| Some(Err(format!("Invalid {} {}", what, value.unwrap_or(""))));
+    match name {
+        "--points" => match value.and_then(|value| value.parse().ok()) {
+            Some(points) if points > 0 => rules.points_to_win = points,
+            _ => return invalid("number of points"),
+        },
+        "--win-by-two" => rules.win_by_two = true,
+        "--time-limit" => match number {
+            Some(seconds) if seconds > 0.0 => rules.time_limit = Some(seconds),
+            _ => return invalid("time limit"),
+        },
+        "--speed-up" => match number {
+            Some(factor) if factor >= 1.0 => speed_up.factor = factor,
+            _ => return invalid("speed up factor"),
+        },
+        "--max-ball-speed" => match number {
+            Some(speed) if speed > 0.0 => speed_up.max_speed = speed,
+            _ => return invalid("max ball speed"),
+        },
+        _ => return None,
+    }
+    Some(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn win_by_two() -> MatchRules {
+        MatchRules {
+            points_to_win: 11,
+            win_by_two: true,
+            ..MatchRules::default()
+        }
+    }
+
+    #[test]
+    fn the_first_to_the_points_wins() {
+        let rules = MatchRules::default();
+        assert_eq!(rules.winner(9, 3, 0.0), None);
+        assert_eq!(rules.winner(10, 9, 0.0), Some(Side::Left));
+        assert_eq!(rules.winner(4, 10, 0.0), Some(Side::Right));
+    }
+
+    #[test]
+    fn win_by_two_plays_on_from_deuce() {
+        let rules = win_by_two();
+        assert_eq!(rules.winner(10, 10, 0.0), None);
+        assert_eq!(rules.winner(11, 10, 0.0), None);
+        assert_eq!(rules.winner(12, 10, 0.0), Some(Side::Left));
+        assert_eq!(rules.winner(13, 15, 0.0), Some(Side::Right));
+    }
+
+    #[test]
+    fn match_point_needs_the_lead_of_two() {
+        let rules = win_by_two();
+        assert!(rules.is_match_point(Side::Left, 10, 5, 0.0));
+        assert!(!rules.is_match_point(Side::Right, 10, 5, 0.0));
+        assert!(!rules.is_match_point(Side::Left, 10, 10, 0.0));
+        assert!(rules.is_match_point(Side::Left, 11, 10, 0.0));
+        assert!(!rules.is_match_point(Side::Right, 11, 10, 0.0));
+        assert!(!rules.is_match_point(Side::Top, 11, 10, 0.0));
+
+        let rules = MatchRules::default();
+        assert!(rules.is_match_point(Side::Right, 9, 9, 0.0));
+    }
+
+    #[test]
+    fn a_tie_at_time_up_goes_into_sudden_death() {
+        let rules = MatchRules {
+            time_limit: Some(60.0),
+            ..MatchRules::default()
+        };
+        assert!(!rules.is_time_up(59.9));
+        assert!(rules.is_time_up(60.0));
+        assert_eq!(rules.remaining_time(45.5), Some(14.5));
+        assert_eq!(rules.remaining_time(70.0), Some(0.0));
+
+        assert_eq!(rules.winner(3, 1, 59.0), None);
+        assert_eq!(rules.winner(3, 1, 60.0), Some(Side::Left));
+        assert_eq!(rules.winner(2, 2, 75.0), None);
+        assert!(rules.is_match_point(Side::Left, 2, 2, 75.0));
+        assert!(rules.is_match_point(Side::Right, 2, 2, 75.0));
+        assert_eq!(rules.winner(2, 3, 75.0), Some(Side::Right));
+    }
+
+    #[test]
+    fn the_last_player_standing_wins() {
+        let rules = MatchRules {
+            mode: MatchMode::FourPlayers,
+            time_limit: Some(60.0),
+            ..MatchRules::default()
+        };
+        assert_eq!(rules.last_standing(&[3, 3, 3, 3], 0.0), None);
+        assert_eq!(rules.last_standing(&[2, 0, 1, 0], 0.0), None);
+        assert_eq!(rules.last_standing(&[0, 0, 1, 0], 0.0), Some(Side::Top));
+
+        // Once time is up the most lives win, a tie for the most goes on.
+        assert_eq!(rules.last_standing(&[2, 0, 1, 0], 60.0), Some(Side::Left));
+        assert_eq!(rules.last_standing(&[2, 2, 1, 0], 60.0), None);
+        assert_eq!(rules.last_standing(&[], 60.0), None);
+    }
+}
@@ -0,0 +1,802 @@
+use crate::physics::{
+    deflect, move_ball, Aabb, Collision, Contact, SpeedUp, Walls, MAX_BOUNCE_ANGLE,
+};
+use crate::rules::{MatchMode, MatchRules};
+
+pub const ARENA_HEIGHT: f32 = 100.0;
+pub const ARENA_WIDTH: f32 = 100.0;
+
+pub const PADDLE_HEIGHT: f32 = 16.0;
+pub const PADDLE_WIDTH: f32 = 4.0;
+pub const PADDLE_SPEED: f32 = 120.0;
+/// Distance between the lanes of a team in doubles.
+pub const LANE_SPACING: f32 = 30.0;
+
+pub const BALL_VELOCITY_X: f32 = 75.0;
+pub const BALL_VELOCITY_Y: f32 = 50.0;
+pub const BALL_RADIUS: f32 = 2.0;
+
+/// Paddles in the largest match layout, inputs for every tick are stored for this many.
+pub const MAX_PLAYERS: usize = 4;
+
+/// The simulation always advances in steps of this many seconds, independent of the frame rate.
+pub const TICK_DELTA: f32 = 1.0 / 60.0;
+// A frame never runs more ticks than this, a long hitch slows the game down instead of
+// freezing it while it catches up.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+    /// Only played in a four-player match, like `Bottom`.
+    Top,
+    Bottom,
+}
+impl Side {
+    /// Every side in `index` order.
+    pub const ALL: [Side; MAX_PLAYERS] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    /// Position of this side's paddle in `Simulation::paddles`.
+    pub fn index(self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+            Side::Top => 2,
+            Side::Bottom => 3,
+        }
+    }
+
+    /// Top and bottom paddles lie flat and move sideways.
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Side::Top | Side::Bottom)
+    }
+}
+
+/// What a player does with their paddle during one tick. The movement is quantized so inputs
+/// can be stored and compared exactly.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PaddleInput {
+    /// `-127` is full speed down, `127` full speed up. Top and bottom paddles move left and right
+    /// instead.
+    pub movement: i8,
+}
+impl PaddleInput {
+    pub fn from_axis(axis: f32) -> PaddleInput {
+        PaddleInput {
+            movement: (axis.clamp(-1.0, 1.0) * 127.0).round() as i8,
+        }
+    }
+
+    pub fn axis(self) -> f32 {
+        f32::from(self.movement) / 127.0
+    }
+
+    /// Moves a paddle at `y` towards `target_y` as fast as a paddle may move in one tick. For
+    /// top and bottom paddles both are x coordinates.
+    pub fn towards(y: f32, target_y: f32) -> PaddleInput {
+        PaddleInput::from_axis((target_y - y) / (PADDLE_SPEED * TICK_DELTA))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimPaddle {
+    /// The team the paddle plays for.
+    pub side: Side,
+    /// See `MatchMode::layout`.
+    pub lane: usize,
+    pub position: [f32; 2],
+    /// Position before the last tick, for interpolating between ticks.
+    pub previous_position: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+}
+impl SimPaddle {
+    fn new(side: Side, lane: usize) -> SimPaddle {
+        let depth = PADDLE_WIDTH * 0.5 + lane as f32 * LANE_SPACING;
+        let position = match side {
+            Side::Left => [depth, ARENA_HEIGHT / 2.0],
+            Side::Right => [ARENA_WIDTH - depth, ARENA_HEIGHT / 2.0],
+            Side::Top => [ARENA_WIDTH / 2.0, ARENA_HEIGHT - depth],
+            Side::Bottom => [ARENA_WIDTH / 2.0, depth],
+        };
+        let (width, height) = if side.is_horizontal() {
+            (PADDLE_HEIGHT, PADDLE_WIDTH)
+        } else {
+            (PADDLE_WIDTH, PADDLE_HEIGHT)
+        };
+        SimPaddle {
+            side,
+            lane,
+            position,
+            previous_position: position,
+            width,
+            height,
+        }
+    }
+
+    /// Index of the coordinate the paddle moves along, `1` for the left and right paddle and
+    /// `0` for the others.
+    pub fn axis(&self) -> usize {
+        if self.side.is_horizontal() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Where the paddle is along its `axis`.
+    pub fn offset(&self) -> f32 {
+        self.position[self.axis()]
+    }
+
+    /// The coordinate of the side facing the other team, x for the left and right paddles and y
+    /// for the others.
+    pub fn face(&self) -> f32 {
+        match self.side {
+            Side::Left => self.position[0] + self.width * 0.5,
+            Side::Right => self.position[0] - self.width * 0.5,
+            Side::Top => self.position[1] - self.height * 0.5,
+            Side::Bottom => self.position[1] + self.height * 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimBall {
+    pub position: [f32; 2],
+    /// Position before the last tick, for interpolating between ticks.
+    pub previous_position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+}
+
+/// Paddle hits since the last point was scored.
+#[derive(Clone, Default, Debug)]
+pub struct Rally {
+    pub hits: u32,
+    pub longest: u32,
+}
+
+/// Where a ball bounced off something.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bounce {
+    /// The ball center at the contact, in arena coordinates.
+    pub position: [f32; 2],
+    /// Arena units per second the ball left with.
+    pub speed: f32,
+}
+impl Bounce {
+    fn from_contact(contact: &Contact) -> Bounce {
+        Bounce {
+            position: contact.position,
+            speed: contact.velocity[0].hypot(contact.velocity[1]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SimEvent {
+    PaddleHit(Side, Bounce),
+    WallHit(Bounce),
+    /// The given side scored a point.
+    Scored(Side),
+    /// The ball got past the given side's paddle in a four-player match.
+    LostLife(Side),
+    /// The given side lost their last life, their goal is walled off from now on.
+    Eliminated(Side),
+    /// The rules ended the match in favor of the given side.
+    MatchOver(Side),
+}
+
+/// The game state that matters for the outcome of a match. It only changes through `step`, so
+/// two simulations fed the same inputs end up in bit-identical states.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub tick: u64,
+    /// Indexed by player, laid out by `MatchMode::layout`.
+    pub paddles: Vec<SimPaddle>,
+    pub balls: Vec<SimBall>,
+    pub score_left: i32,
+    pub score_right: i32,
+    /// Lives left in a four-player match, indexed by `Side::index`. Empty otherwise.
+    pub lives: Vec<i32>,
+    pub rally: Rally,
+    /// Set once the rules decided the match, the simulation stands still from then on.
+    pub winner: Option<Side>,
+    pub rules: MatchRules,
+    pub speed_up: SpeedUp,
+}
+impl Simulation {
+    pub fn new(rules: MatchRules, speed_up: SpeedUp) -> Simulation {
+        Simulation {
+            tick: 0,
+            paddles: rules
+                .mode
+                .layout()
+                .iter()
+                .map(|(side, lane)| SimPaddle::new(*side, *lane))
+                .collect(),
+            balls: Vec::new(),
+            score_left: 0,
+            score_right: 0,
+            lives: if rules.mode == MatchMode::FourPlayers {
+                vec![rules.lives; rules.players()]
+            } else {
+                Vec::new()
+            },
+            rally: Rally::default(),
+            winner: None,
+            rules,
+            speed_up,
+        }
+    }
+
+    /// Back to the start of a match, keeping the configuration.
+    pub fn reset(&mut self) {
+        *self = Simulation::new(self.rules.clone(), self.speed_up.clone());
+    }
+
+    /// Seconds of play so far.
+    pub fn elapsed(&self) -> f32 {
+        self.tick as f32 * TICK_DELTA
+    }
+
+    fn has_paddle(&self, side: Side) -> bool {
+        self.paddles.iter().any(|paddle| paddle.side == side)
+    }
+
+    /// True once the side ran out of lives in a four-player match.
+    pub fn is_eliminated(&self, side: Side) -> bool {
+        is_eliminated(&self.lives, side)
+    }
+
+    /// Borders without a goal are walls: the top and bottom of a two-player match, and the
+    /// goals of eliminated players, walled off where their paddle used to be.
+    pub fn walls(&self) -> Walls {
+        let wall = |side: Side, border: f32, front: f32| {
+            if !self.has_paddle(side) {
+                Some(border)
+            } else if self.is_eliminated(side) {
+                Some(front)
+            } else {
+                None
+            }
+        };
+        Walls {
+            left: wall(Side::Left, 0.0, PADDLE_WIDTH),
+            right: wall(Side::Right, ARENA_WIDTH, ARENA_WIDTH - PADDLE_WIDTH),
+            bottom: wall(Side::Bottom, 0.0, PADDLE_WIDTH),
+            top: wall(Side::Top, ARENA_HEIGHT, ARENA_HEIGHT - PADDLE_WIDTH),
+        }
+    }
+
+    /// Puts a new ball into the center of the arena.
+    pub fn serve(&mut self) {
+        let position = [ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0];
+        self.balls.push(SimBall {
+            position,
+            previous_position: position,
+            velocity: [BALL_VELOCITY_X, BALL_VELOCITY_Y],
+            radius: BALL_RADIUS,
+        });
+    }
+
+    /// Advances the game by `TICK_DELTA`. `inputs` are indexed like `paddles`, missing inputs
+    /// leave the paddle where it is.
+    pub fn step(&mut self, inputs: &[PaddleInput]) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        if self.winner.is_some() {
+            return events;
+        }
+        self.tick += 1;
+
+        // The corners belong to nobody in a four-player match, paddles stay out of them.
+        let corner = if self.rules.mode == MatchMode::FourPlayers {
+            PADDLE_WIDTH
+        } else {
+            0.0
+        };
+        for (index, paddle) in self.paddles.iter_mut().enumerate() {
+            let input = inputs.get(index).copied().unwrap_or_default();
+            paddle.previous_position = paddle.position;
+            let axis = paddle.axis();
+            let length = if axis == 0 {
+                paddle.width
+            } else {
+                paddle.height
+            };
+            let extent = if axis == 0 { ARENA_WIDTH } else { ARENA_HEIGHT };
+            paddle.position[axis] = clamp_along(
+                paddle.position[axis] + input.axis() * PADDLE_SPEED * TICK_DELTA,
+                length,
+                extent,
+                corner,
+            );
+        }
+
+        // Eliminated paddles are gone, their goal is a wall now.
+        let active = self
+            .paddles
+            .iter()
+            .filter(|paddle| !self.is_eliminated(paddle.side))
+            .collect::<Vec<_>>();
+        let sides = active.iter().map(|paddle| paddle.side).collect::<Vec<_>>();
+        let aabbs = active
+            .iter()
+            .map(|paddle| Aabb::from_center(paddle.position, paddle.width, paddle.height))
+            .collect::<Vec<_>>();
+        let walls = self.walls();
+        let goals = Side::ALL
+            .iter()
+            .map(|side| self.has_paddle(*side))
+            .collect::<Vec<_>>();
+
+        let rally = &mut self.rally;
+        let speed_up = &self.speed_up;
+        for ball in self.balls.iter_mut() {
+            let radius = ball.radius;
+            ball.previous_position = ball.position;
+
+            let contacts = move_ball(
+                &mut ball.position,
+                &mut ball.velocity,
+                radius,
+                TICK_DELTA,
+                &walls,
+                &aabbs,
+                |index, position, velocity| {
+                    // Only return balls that are heading towards the paddle's goal, shots of the
+                    // own team pass through.
+                    let side = sides[index];
+                    let incoming = match side {
+                        Side::Left => velocity[0] < 0.0,
+                        Side::Right => velocity[0] > 0.0,
+                        Side::Top => velocity[1] > 0.0,
+                        Side::Bottom => velocity[1] < 0.0,
+                    };
+                    if !incoming {
+                        return None;
+                    }
+
+                    // The further from the center the paddle is hit, the steeper the ball leaves.
+                    // Flat paddles work the same with the axes swapped.
+                    let along = if side.is_horizontal() { 0 } else { 1 };
+                    let paddle = &aabbs[index];
+                    let half_length = (paddle.max[along] - paddle.min[along]) * 0.5;
+                    let offset =
+                        (position[along] - paddle.center()[along]) / (half_length + radius);
+                    rally.hits += 1;
+                    rally.longest = rally.longest.max(rally.hits);
+                    let deflected = if side.is_horizontal() {
+                        let [y, x] = deflect([velocity[1], velocity[0]], offset, MAX_BOUNCE_ANGLE);
+                        [x, y]
+                    } else {
+                        deflect(velocity, offset, MAX_BOUNCE_ANGLE)
+                    };
+                    Some(speed_up.apply(deflected))
+                },
+            );
+            events.extend(contacts.iter().map(|contact| {
+                let bounce = Bounce::from_contact(contact);
+                match contact.collision {
+                    Collision::Wall => SimEvent::WallHit(bounce),
+                    Collision::Paddle(index) => SimEvent::PaddleHit(sides[index], bounce),
+                }
+            }));
+
+            // The side whose goal the ball went into.
+            let lives = &self.lives;
+            let conceded = [
+                (Side::Left, ball.position[0] <= radius),
+                (Side::Right, ball.position[0] >= ARENA_WIDTH - radius),
+                (Side::Bottom, ball.position[1] <= radius),
+                (Side::Top, ball.position[1] >= ARENA_HEIGHT - radius),
+            ]
+            .iter()
+            .find(|(side, out)| *out && goals[side.index()] && !is_eliminated(lives, *side))
+            .map(|(side, _)| *side);
+
+            if let Some(side) = conceded {
+                if self.rules.mode == MatchMode::FourPlayers {
+                    let lives = &mut self.lives[side.index()];
+                    *lives -= 1;
+                    events.push(SimEvent::LostLife(side));
+                    if *lives == 0 {
+                        events.push(SimEvent::Eliminated(side));
+                    }
+                } else {
+                    // We top the score at 999 to avoid text overlap.
+                    let scorer = match side {
+                        Side::Right => {
+                            self.score_left = (self.score_left + 1).min(999);
+                            Side::Left
+                        }
+                        _ => {
+                            self.score_right = (self.score_right + 1).min(999);
+                            Side::Right
+                        }
+                    };
+                    events.push(SimEvent::Scored(scorer));
+                }
+
+                // Reverse direction and start the next rally from the center at the initial
+                // speed again.
+                let mut direction = [ball.velocity[0].signum(), ball.velocity[1].signum()];
+                let axis = if side.is_horizontal() { 1 } else { 0 };
+                direction[axis] = -direction[axis];
+                ball.velocity = [
+                    direction[0] * BALL_VELOCITY_X,
+                    direction[1] * BALL_VELOCITY_Y,
+                ];
+                ball.position = [ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0];
+                ball.previous_position = ball.position;
+                rally.hits = 0;
+            }
+        }
+
+        // The time limit can end the match without a point being scored.
+        self.winner = if self.rules.mode == MatchMode::FourPlayers {
+            self.rules.last_standing(&self.lives, self.elapsed())
+        } else {
+            self.rules
+                .winner(self.score_left, self.score_right, self.elapsed())
+        };
+        if let Some(side) = self.winner {
+            events.push(SimEvent::MatchOver(side));
+        }
+
+        events
+    }
+
+    /// Fingerprint of the complete state, equal hashes mean bit-identical simulations.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::default();
+        hasher.write(self.tick);
+        for paddle in &self.paddles {
+            hasher.write_floats(&paddle.position);
+        }
+        for ball in &self.balls {
+            hasher.write_floats(&ball.position);
+            hasher.write_floats(&ball.velocity);
+        }
+        hasher.write(self.score_left as u64);
+        hasher.write(self.score_right as u64);
+        for lives in &self.lives {
+            hasher.write(*lives as u64);
+        }
+        hasher.write(u64::from(self.rally.hits));
+        hasher.write(self.winner.map_or(0, |side| side.index() as u64 + 1));
+        hasher.0
+    }
+}
+
+// FNV-1a, stable across runs and platforms unlike the std hasher.
+struct StateHasher(u64);
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+impl StateHasher {
+    fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes().iter() {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_floats(&mut self, values: &[f32]) {
+        for value in values {
+            self.write(u64::from(value.to_bits()));
+        }
+    }
+}
+
+/// Keeps the paddle fully inside the arena.
+pub fn clamp_paddle_y(y: f32, paddle_height: f32) -> f32 {
+    clamp_along(y, paddle_height, ARENA_HEIGHT, 0.0)
+}
+
+// Keeps a paddle of the given length between the corners of a border of the given extent.
+fn clamp_along(value: f32, length: f32, extent: f32, corner: f32) -> f32 {
+    value
+        .min(extent - corner - length * 0.5)
+        .max(corner + length * 0.5)
+}
+
+fn is_eliminated(lives: &[i32], side: Side) -> bool {
+    matches!(lives.get(side.index()), Some(lives) if *lives <= 0)
+}
+
+/// Turns variable frame times into a number of fixed ticks to simulate.
+#[derive(Default)]
+pub struct TickClock {
+    accumulator: f32,
+    /// How far the current frame is between the last two ticks, from `0.0` to `1.0`. Used to
+    /// interpolate what is rendered.
+    pub alpha: f32,
+}
+impl TickClock {
+    /// Adds the frame time and returns how many ticks to simulate for it.
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        self.accumulator += delta;
+
+        let mut ticks = 0;
+        while self.accumulator >= TICK_DELTA {
+            self.accumulator -= TICK_DELTA;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_FRAME {
+            ticks = MAX_TICKS_PER_FRAME;
+            self.accumulator = 0.0;
+        }
+
+        self.alpha = self.accumulator / TICK_DELTA;
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AiController, AiDifficulty, AiPresets};
+    use crate::utils::Rng;
+
+    // Scores stop at 999, so these rules never end the match.
+    fn endless_match() -> Simulation {
+        let rules = MatchRules {
+            points_to_win: 1000,
+            ..MatchRules::default()
+        };
+        Simulation::new(rules, SpeedUp::default())
+    }
+
+    fn random_inputs(seed: u64, ticks: usize) -> Vec<[PaddleInput; 2]> {
+        let mut rng = Rng::new(seed);
+        (0..ticks)
+            .map(|_| {
+                [
+                    PaddleInput::from_axis(rng.range(-1.0, 1.0)),
+                    PaddleInput::from_axis(rng.range(-1.0, 1.0)),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_inputs_give_identical_states() {
+        let inputs = random_inputs(42, 20_000);
+        let mut first = endless_match();
+        let mut second = endless_match();
+        first.serve();
+        second.serve();
+
+        let initial_hash = first.state_hash();
+        for tick_inputs in &inputs {
+            let first_events = first.step(tick_inputs);
+            let second_events = second.step(tick_inputs);
+            assert_eq!(first_events, second_events);
+            assert_eq!(
+                first.state_hash(),
+                second.state_hash(),
+                "tick {}",
+                first.tick
+            );
+        }
+
+        assert_ne!(first.state_hash(), initial_hash);
+        assert!(first.score_left + first.score_right > 0);
+    }
+
+    #[test]
+    fn different_inputs_diverge() {
+        let mut first = endless_match();
+        let mut second = endless_match();
+        first.serve();
+        second.serve();
+
+        for (a, b) in random_inputs(1, 600)
+            .iter()
+            .zip(random_inputs(2, 600).iter())
+        {
+            first.step(a);
+            second.step(b);
+        }
+        assert_ne!(first.state_hash(), second.state_hash());
+    }
+
+    #[test]
+    fn thousands_of_computer_matches_finish() {
+        let presets = AiPresets::default();
+        let rules = MatchRules {
+            points_to_win: 3,
+            ..MatchRules::default()
+        };
+
+        for seed in 0..2000 {
+            let mut left =
+                AiController::new(presets.get(AiDifficulty::Normal).clone(), Rng::new(seed));
+            let mut right =
+                AiController::new(presets.get(AiDifficulty::Easy).clone(), Rng::new(!seed));
+            let mut simulation = Simulation::new(rules.clone(), SpeedUp::default());
+            simulation.serve();
+
+            let mut match_over = None;
+            // Ten minutes of play is far more than three points ever take.
+            while match_over.is_none() && simulation.tick < 36_000 {
+                let inputs = [left.input(&simulation, 0), right.input(&simulation, 1)];
+                for event in simulation.step(&inputs) {
+                    if let SimEvent::MatchOver(side) = event {
+                        match_over = Some(side);
+                    }
+                }
+
+                for ball in &simulation.balls {
+                    assert!(ball.position[1] >= ball.radius - 0.001);
+                    assert!(ball.position[1] <= ARENA_HEIGHT - ball.radius + 0.001);
+                }
+            }
+
+            assert!(match_over.is_some(), "match {} never ended", seed);
+            assert_eq!(match_over, simulation.winner);
+            assert_eq!(simulation.score_left.max(simulation.score_right), 3);
+            assert!(simulation.step(&[]).is_empty());
+        }
+    }
+
+    #[test]
+    fn four_player_matches_end_with_the_last_player_standing() {
+        let presets = AiPresets::default();
+        let rules = MatchRules {
+            mode: MatchMode::FourPlayers,
+            lives: 2,
+            ..MatchRules::default()
+        };
+
+        for seed in 0..200 {
+            let mut players = Side::ALL
+                .iter()
+                .map(|side| {
+                    AiController::new(
+                        presets.get(AiDifficulty::Easy).clone(),
+                        Rng::new(seed * 4 + side.index() as u64),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut simulation = Simulation::new(rules.clone(), SpeedUp::default());
+            simulation.serve();
+
+            let mut eliminated = Vec::new();
+            while simulation.winner.is_none() && simulation.tick < 36_000 {
+                let inputs = players
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(player, ai)| ai.input(&simulation, player))
+                    .collect::<Vec<_>>();
+                for event in simulation.step(&inputs) {
+                    if let SimEvent::Eliminated(side) = event {
+                        eliminated.push(side);
+                    }
+                }
+
+                // Walled off goals keep the ball in.
+                let walls = simulation.walls();
+                for ball in &simulation.balls {
+                    let [x, y] = ball.position;
+                    let margin = ball.radius - 0.001;
+                    assert!(!matches!(walls.left, Some(wall) if x < wall + margin));
+                    assert!(!matches!(walls.right, Some(wall) if x > wall - margin));
+                    assert!(!matches!(walls.bottom, Some(wall) if y < wall + margin));
+                    assert!(!matches!(walls.top, Some(wall) if y > wall - margin));
+                }
+            }
+
+            let winner = simulation.winner.expect("match never ended");
+            assert_eq!(eliminated.len(), 3, "match {}", seed);
+            assert!(!eliminated.contains(&winner));
+            assert!(simulation.lives[winner.index()] > 0);
+            assert!(!simulation.is_eliminated(winner));
+        }
+    }
+
+    #[test]
+    fn doubles_teams_return_the_ball_from_either_lane() {
+        let rules = MatchRules {
+            mode: MatchMode::Doubles,
+            ..MatchRules::default()
+        };
+        let mut simulation = Simulation::new(rules, SpeedUp::default());
+        let front = &simulation.paddles[2];
+        assert_eq!((front.side, front.lane), (Side::Left, 1));
+        let lane_x = front.position[0];
+
+        // A shot of the left team passes its own front paddle.
+        let position = [lane_x - 4.0, ARENA_HEIGHT / 2.0];
+        simulation.balls.push(SimBall {
+            position,
+            previous_position: position,
+            velocity: [BALL_VELOCITY_X, 0.0],
+            radius: BALL_RADIUS,
+        });
+        for _ in 0..10 {
+            simulation.step(&[]);
+        }
+        assert!(simulation.balls[0].velocity[0] > 0.0);
+        assert!(simulation.balls[0].position[0] > lane_x);
+
+        // The front paddle returns it on the way back, before the back paddle sees it.
+        simulation.balls[0].velocity = [-BALL_VELOCITY_X, 0.0];
+        let mut hit = None;
+        for _ in 0..20 {
+            for event in simulation.step(&[]) {
+                if let SimEvent::PaddleHit(side, _) = event {
+                    hit = Some(side);
+                }
+            }
+        }
+        assert_eq!(hit, Some(Side::Left));
+        assert!(simulation.balls[0].velocity[0] > 0.0);
+        assert!(simulation.balls[0].position[0] > lane_x);
+        assert_eq!(simulation.score_right, 0);
+    }
+
+    #[test]
+    fn rallies_speed_the_ball_up_until_a_point() {
+        let mut simulation = Simulation::new(MatchRules::default(), SpeedUp::default());
+        let serve_speed = BALL_VELOCITY_X.hypot(BALL_VELOCITY_Y);
+        let paddle = simulation.paddles[0].position;
+        let position = [paddle[0] + 10.0, paddle[1]];
+        simulation.balls.push(SimBall {
+            position,
+            previous_position: position,
+            velocity: [-BALL_VELOCITY_X, -BALL_VELOCITY_Y],
+            radius: BALL_RADIUS,
+        });
+
+        let mut hits = 0;
+        while hits == 0 {
+            hits += simulation
+                .step(&[])
+                .iter()
+                .filter(|event| matches!(event, SimEvent::PaddleHit(Side::Left, _)))
+                .count();
+        }
+        let ball = &simulation.balls[0];
+        let speed = ball.velocity[0].hypot(ball.velocity[1]);
+        assert!((speed - serve_speed * SpeedUp::default().factor).abs() < 1e-3);
+        assert_eq!(simulation.rally.hits, 1);
+
+        // Nobody returns it on the right.
+        let mut points = 0;
+        while points == 0 {
+            points += simulation
+                .step(&[])
+                .iter()
+                .filter(|event| matches!(event, SimEvent::Scored(_)))
+                .count();
+        }
+        let ball = &simulation.balls[0];
+        assert!((ball.velocity[0].hypot(ball.velocity[1]) - serve_speed).abs() < 1e-3);
+        assert_eq!(simulation.rally.hits, 0);
+        assert_eq!(simulation.rally.longest, 1);
+    }
+
+    #[test]
+    fn tick_count_does_not_depend_on_frame_rate() {
+        for &fps in &[30.0f32, 60.0, 144.0, 240.0] {
+            let mut clock = TickClock::default();
+            let mut ticks = 0;
+            // Ten seconds in frames of this rate.
+            for _ in 0..(fps as u32 * 10) {
+                ticks += clock.advance(1.0 / fps);
+            }
+            assert!(
+                (ticks as i64 - 600).abs() <= 1,
+                "{} fps ran {} ticks",
+                fps,
+                ticks
+            );
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::simulation::{ARENA_WIDTH, BALL_VELOCITY_X, BALL_VELOCITY_Y};
+use crate::utils::{asset_path, Ini, IniSection, Rng};
+use std::fs;
+
+const THEMES_FILE: &str = "audio/themes.ini";
+// Shipped themes, used when the file in the assets folder is missing or broken.
+const DEFAULT_THEMES: &str = include_str!("../assets/audio/themes.ini");
+
+/// Where and how fast a sound effect plays.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Playback {
+    /// From `-1.0`, only the left speaker, to `1.0`, only the right one.
+    pub pan: f32,
+    /// Playback rate, `2.0` plays the sound twice as fast and an octave higher.
+    pub pitch: f32,
+}
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            pan: 0.0,
+            pitch: 1.0,
+        }
+    }
+}
+impl Playback {
+    /// Gains of the left and right channel. The center plays at full volume on both speakers,
+    /// towards the sides the other speaker fades out.
+    pub fn channel_gains(self) -> [f32; 2] {
+        let pan = self.pan.clamp(-1.0, 1.0);
+        [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)]
+    }
+}
+
+/// A collision sound of a theme.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HitSound {
+    /// Name of the effect in the sound manifest.
+    pub effect: String,
+    pub pitch: f32,
+}
+
+/// How the collision sounds react to the ball, see `assets/audio/themes.ini`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoundTheme {
+    pub name: String,
+    pub paddle: HitSound,
+    pub wall: HitSound,
+    pub pan: f32,
+    pub pitch_per_speed: f32,
+    pub max_pitch: f32,
+    pub pitch_variation: f32,
+}
+impl SoundTheme {
+    fn parse(section: &IniSection) -> Result<SoundTheme, String> {
+        let fraction = |key: &str| {
+            let value: f32 = section.parse(key)?;
+            if (0.0..=1.0).contains(&value) {
+                Ok(value)
+            } else {
+                Err(format!(
+                    "[{}] `{}` must be between 0 and 1, got {}",
+                    section.name, key, value
+                ))
+            }
+        };
+        let pitch = |key: &str| {
+            let value: f32 = section.parse(key)?;
+            if value > 0.0 {
+                Ok(value)
+            } else {
+                Err(format!(
+                    "[{}] `{}` must be above 0, got {}",
+                    section.name, key, value
+                ))
+            }
+        };
+        Ok(SoundTheme {
+            name: section.name.clone(),
+            paddle: HitSound {
+                effect: section.parse("paddle_sound")?,
+                pitch: pitch("paddle_pitch")?,
+            },
+            wall: HitSound {
+                effect: section.parse("wall_sound")?,
+                pitch: pitch("wall_pitch")?,
+            },
+            pan: fraction("pan")?,
+            pitch_per_speed: section.parse("pitch_per_speed")?,
+            max_pitch: pitch("max_pitch")?,
+            pitch_variation: fraction("pitch_variation")?,
+        })
+    }
+
+    /// How `sound` plays for a ball at `position` going `speed` arena units per second.
+    pub fn hit(&self, sound: &HitSound, position: [f32; 2], speed: f32, rng: &mut Rng) -> Playback {
+        let serve_speed = BALL_VELOCITY_X.hypot(BALL_VELOCITY_Y);
+        // A ball slower than the serve lowers the pitch, but never below half.
+        let speed_pitch = (1.0 + (speed - serve_speed) * self.pitch_per_speed)
+            .min(self.max_pitch)
+            .max(0.5);
+        let variation = 1.0 + rng.range(-self.pitch_variation, self.pitch_variation);
+        Playback {
+            pan: (position[0] / ARENA_WIDTH * 2.0 - 1.0).clamp(-1.0, 1.0) * self.pan,
+            pitch: sound.pitch * speed_pitch * variation,
+        }
+    }
+}
+
+/// All sound themes, in the order of their file.
+pub struct SoundThemes {
+    themes: Vec<SoundTheme>,
+}
+impl SoundThemes {
+    /// Loads the themes from the assets folder, falling back to the shipped ones.
+    pub fn load() -> SoundThemes {
+        let path = asset_path(THEMES_FILE);
+        let loaded = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| SoundThemes::parse(&source));
+        match loaded {
+            Ok(themes) => themes,
+            Err(error) => {
+                eprintln!(
+                    "Could not load sound themes from {}: {}",
+                    path.display(),
+                    error
+                );
+                SoundThemes::default()
+            }
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<SoundThemes, String> {
+        let ini = Ini::parse(source).map_err(|error| error.to_string())?;
+        let themes = ini
+            .sections()
+            .map(SoundTheme::parse)
+            .collect::<Result<Vec<_>, String>>()?;
+        if themes.is_empty() {
+            return Err("no themes".to_string());
+        }
+        Ok(SoundThemes { themes })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SoundTheme> {
+        self.themes.iter().find(|theme| theme.name == name)
+    }
+
+    /// The theme played unless another one is picked, the first in the file.
+    pub fn first(&self) -> &SoundTheme {
+        &self.themes[0]
+    }
+}
+impl Default for SoundThemes {
+    fn default() -> Self {
+        SoundThemes::parse(DEFAULT_THEMES).expect("shipped sound themes are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_follow_the_ball() {
+        let themes = SoundThemes::default();
+        let theme = themes.get("classic").unwrap();
+        let mut rng = Rng::new(5);
+        let serve_speed = BALL_VELOCITY_X.hypot(BALL_VELOCITY_Y);
+
+        let left = theme.hit(&theme.paddle, [0.0, 50.0], serve_speed, &mut rng);
+        assert!((left.pan + theme.pan).abs() < 0.001, "{:?}", left);
+        let [left_gain, right_gain] = left.channel_gains();
+        assert_eq!(left_gain, 1.0);
+        assert!(right_gain < 1.0);
+        let variation = theme.pitch_variation + 0.001;
+        assert!((left.pitch - 1.0).abs() < variation, "{:?}", left);
+
+        let fast = theme.hit(&theme.wall, [100.0, 50.0], 1000.0, &mut rng);
+        assert!(fast.pan > 0.0);
+        assert!(fast.pitch <= theme.wall.pitch * theme.max_pitch * (1.0 + variation));
+        assert!(fast.pitch > theme.wall.pitch, "{:?}", fast);
+
+        assert_eq!(Playback::default().channel_gains(), [1.0, 1.0]);
+        let flat = themes.get("flat").unwrap();
+        assert_eq!(
+            flat.hit(&flat.wall, [10.0, 10.0], 150.0, &mut rng),
+            Playback::default()
+        );
+        assert_ne!(theme.wall.effect, theme.paddle.effect);
+        assert_eq!(themes.first().name, "classic");
+        assert_eq!(themes.get("loud"), None);
+    }
+
+    #[test]
+    fn broken_themes_are_rejected() {
+        assert!(SoundThemes::parse("").is_err());
+        let error = SoundThemes::parse(&DEFAULT_THEMES.replace("pan = 0.8", "pan = 3"))
+            .err()
+            .unwrap();
+        assert!(error.contains("[classic]"), "{}", error);
+    }
+}
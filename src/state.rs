@@ -0,0 +1,79 @@
+use bevy::ecs::{Commands, Entity, Query, ResMut};
+use bevy::prelude::DespawnRecursiveExt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AppState {
+    MainMenu,
+    Settings,
+    /// Controls the background music.
+    Music,
+    Volume,
+    /// Finding or hosting a match on the local network.
+    Lobby,
+    /// Waiting for the other peer of an online match.
+    Connecting,
+    Countdown,
+    Playing,
+    Paused,
+    GameOver,
+    ReplayViewer,
+}
+
+/// The phase the app is in. Transitions requested with `set` are applied at the start of the
+/// next frame so every system of a frame sees the same state. Setting the current state again
+/// re-enters it.
+pub struct GameState {
+    current: AppState,
+    next: Option<AppState>,
+    entered: bool,
+}
+impl GameState {
+    pub fn new(initial: AppState) -> GameState {
+        // The initial state is entered on the first frame like any other transition.
+        GameState {
+            current: initial,
+            next: Some(initial),
+            entered: false,
+        }
+    }
+
+    pub fn current(&self) -> AppState {
+        self.current
+    }
+
+    pub fn is(&self, state: AppState) -> bool {
+        self.current == state
+    }
+
+    /// True during the first frame after `state` was entered.
+    pub fn just_entered(&self, state: AppState) -> bool {
+        self.entered && self.current == state
+    }
+
+    pub fn set(&mut self, state: AppState) {
+        self.next = Some(state);
+    }
+}
+
+/// Entities with this component are despawned (including their children) as soon as the app
+/// switches to a state that is not listed.
+pub struct StateScoped(pub &'static [AppState]);
+
+pub fn state_transition_system(
+    commands: &mut Commands,
+    mut state: ResMut<GameState>,
+    query: Query<(Entity, &StateScoped)>,
+) {
+    state.entered = false;
+
+    if let Some(next) = state.next.take() {
+        state.current = next;
+        state.entered = true;
+
+        for (entity, scope) in query.iter() {
+            if !scope.0.contains(&next) {
+                commands.despawn_recursive(entity);
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::game::NetworkMatch;
+use bevy::app::{EventReader, Events};
+use bevy::ecs::{Local, Res, ResMut};
+use simple_pong_bevy::simulation::{SimEvent, Simulation};
+
+/// Streams the arena and the frame's events to the spectators of `--broadcast`, in every state,
+/// so they see exactly what is shown here.
+pub fn broadcast_system(
+    mut event_reader: Local<EventReader<SimEvent>>,
+    events: Res<Events<SimEvent>>,
+    simulation: Res<Simulation>,
+    mut network: ResMut<NetworkMatch>,
+) {
+    let frame_events: Vec<SimEvent> = event_reader.iter(&events).copied().collect();
+    let broadcaster = match &mut network.broadcaster {
+        Some(broadcaster) => broadcaster,
+        None => return,
+    };
+
+    let spectators = broadcaster.spectators();
+    if let Err(error) = broadcaster.update(&simulation, frame_events) {
+        eprintln!("Stopped broadcasting: {}", error);
+        network.broadcaster = None;
+        return;
+    }
+    if broadcaster.spectators() != spectators {
+        eprintln!("Spectators: {}", broadcaster.spectators());
+    }
+}
@@ -0,0 +1,36 @@
+use bevy::app::{EventReader, Events};
+use bevy::ecs::{Local, Res, ResMut};
+use simple_pong_bevy::events::{BallHitPaddle, BallHitWall, GameEvent, MatchWon, PointScored};
+use simple_pong_bevy::simulation::{Side, SimEvent, Simulation};
+
+/// Publishes the simulation's events as the typed game events that scores and sounds react to.
+///
+/// The match is won once the shown simulation has its winner rather than on its `MatchOver`
+/// event. Online a predicted win can still be rolled back and the server's state that ended the
+/// match can get lost.
+pub fn game_events_system(
+    mut event_reader: Local<EventReader<SimEvent>>,
+    mut announced_winner: Local<Option<Side>>,
+    events: Res<Events<SimEvent>>,
+    simulation: Res<Simulation>,
+    mut paddle_hits: ResMut<Events<BallHitPaddle>>,
+    mut wall_hits: ResMut<Events<BallHitWall>>,
+    mut points: ResMut<Events<PointScored>>,
+    mut wins: ResMut<Events<MatchWon>>,
+) {
+    let frame_events = event_reader.iter(&events).copied().collect::<Vec<_>>();
+    for event in GameEvent::from_sim_events(&frame_events) {
+        match event {
+            GameEvent::BallHitPaddle(hit) => paddle_hits.send(hit),
+            GameEvent::BallHitWall(hit) => wall_hits.send(hit),
+            GameEvent::PointScored(point) => points.send(point),
+        }
+    }
+
+    if simulation.winner != *announced_winner {
+        *announced_winner = simulation.winner;
+        if let Some(winner) = simulation.winner {
+            wins.send(MatchWon { winner });
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::input::GamepadAssignments;
+use crate::state::{AppState, GameState};
+use bevy::app::{EventReader, Events};
+use bevy::ecs::{Local, Res, ResMut};
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+
+/// Hands newly connected gamepads to the players. Losing a player's pad pauses the match so they
+/// can plug it back in.
+pub fn gamepad_connection_system(
+    mut event_reader: Local<EventReader<GamepadEvent>>,
+    events: Res<Events<GamepadEvent>>,
+    mut assignments: ResMut<GamepadAssignments>,
+    mut state: ResMut<GameState>,
+) {
+    for GamepadEvent(gamepad, event_type) in event_reader.iter(&events) {
+        match event_type {
+            GamepadEventType::Connected => {
+                if let Some(side) = assignments.assign(*gamepad) {
+                    eprintln!("Gamepad {} connected for the {:?} paddle", gamepad.0, side);
+                }
+            }
+            GamepadEventType::Disconnected => {
+                if let Some(side) = assignments.unassign(*gamepad) {
+                    eprintln!(
+                        "Gamepad {} of the {:?} paddle disconnected",
+                        gamepad.0, side
+                    );
+                    if state.is(AppState::Playing) {
+                        state.set(AppState::Paused);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
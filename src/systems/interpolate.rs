@@ -0,0 +1,126 @@
+use crate::cli::Controller;
+use crate::game::{
+    initialise_ball, initialise_paddle, Ball, MatchReplay, MatchSetup, Paddle, SpriteSheet,
+};
+use crate::state::{AppState, GameState};
+use bevy::ecs::{Commands, Entity, Query, Res};
+use bevy::prelude::{Quat, Transform, Vec3};
+use simple_pong_bevy::ai::{AiController, AiPresets};
+use simple_pong_bevy::simulation::{
+    Simulation, TickClock, ARENA_HEIGHT, ARENA_WIDTH, PADDLE_HEIGHT,
+};
+use simple_pong_bevy::utils::Rng;
+use std::f32::consts::FRAC_PI_2;
+
+/// Places the paddles between their last two simulated positions, so movement looks smooth no
+/// matter how the frame rate relates to the tick rate. Keeps one paddle entity per player of
+/// the match, eliminated ones stretch into the wall in front of their goal. The countdown
+/// clears the paddles, so every match gets its computer players from the current setup.
+pub fn interpolate_paddles_system(
+    commands: &mut Commands,
+    clock: Res<TickClock>,
+    simulation: Res<Simulation>,
+    sprite_sheet: Res<SpriteSheet>,
+    setup: Res<MatchSetup>,
+    ai_presets: Res<AiPresets>,
+    match_replay: Res<MatchReplay>,
+    mut query: Query<(Entity, &Paddle, &mut Transform)>,
+) {
+    let mut rendered = vec![false; simulation.paddles.len()];
+    for (entity, paddle, mut transform) in query.iter_mut() {
+        let sim_paddle = match simulation.paddles.get(paddle.player) {
+            Some(sim_paddle) if paddle.shows(sim_paddle) => sim_paddle,
+            _ => {
+                commands.despawn(entity);
+                continue;
+            }
+        };
+        rendered[paddle.player] = true;
+
+        // Flat paddles are the upright sprite turned on its side.
+        transform.rotation = if paddle.side.is_horizontal() {
+            Quat::from_rotation_z(FRAC_PI_2)
+        } else {
+            Quat::identity()
+        };
+
+        let axis = sim_paddle.axis();
+        let [x, y] = if simulation.is_eliminated(paddle.side) {
+            let extent = if axis == 0 { ARENA_WIDTH } else { ARENA_HEIGHT };
+            transform.scale = Vec3::new(1.0, extent / PADDLE_HEIGHT, 1.0);
+            let mut position = sim_paddle.position;
+            position[axis] = extent / 2.0;
+            position
+        } else {
+            transform.scale = Vec3::one();
+            lerp(
+                sim_paddle.previous_position,
+                sim_paddle.position,
+                clock.alpha,
+            )
+        };
+        transform.translation.x = x;
+        transform.translation.y = y;
+    }
+
+    for (player, _) in rendered
+        .iter()
+        .enumerate()
+        .filter(|(_, rendered)| !**rendered)
+    {
+        initialise_paddle(
+            commands,
+            &sprite_sheet.handle,
+            player,
+            &simulation.paddles[player],
+        );
+        if setup.controllers[player] == Controller::Computer {
+            commands.with(AiController::new(
+                ai_presets.get(setup.difficulty).clone(),
+                Rng::new(match_replay.replay.seed ^ player as u64),
+            ));
+        }
+    }
+}
+
+/// Keeps one ball entity per simulated ball and interpolates them like the paddles.
+pub fn interpolate_balls_system(
+    commands: &mut Commands,
+    state: Res<GameState>,
+    clock: Res<TickClock>,
+    simulation: Res<Simulation>,
+    sprite_sheet: Res<SpriteSheet>,
+    mut query: Query<(Entity, &Ball, &mut Transform)>,
+) {
+    let mut rendered = vec![false; simulation.balls.len()];
+    for (entity, ball, mut transform) in query.iter_mut() {
+        match simulation.balls.get(ball.index) {
+            Some(sim_ball) => {
+                let [x, y] = lerp(sim_ball.previous_position, sim_ball.position, clock.alpha);
+                transform.translation.x = x;
+                transform.translation.y = y;
+                rendered[ball.index] = true;
+            }
+            None => commands.despawn(entity),
+        }
+    }
+
+    // Balls only show up during a match or its replay, the state cleans them up afterwards.
+    if state.is(AppState::Playing) || state.is(AppState::Paused) || state.is(AppState::ReplayViewer)
+    {
+        for (index, _) in rendered
+            .iter()
+            .enumerate()
+            .filter(|(_, rendered)| !**rendered)
+        {
+            initialise_ball(commands, &sprite_sheet.handle, index);
+        }
+    }
+}
+
+fn lerp(from: [f32; 2], to: [f32; 2], alpha: f32) -> [f32; 2] {
+    [
+        from[0] + (to[0] - from[0]) * alpha,
+        from[1] + (to[1] - from[1]) * alpha,
+    ]
+}
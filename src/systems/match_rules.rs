@@ -0,0 +1,53 @@
+use crate::game::ScoreText;
+use crate::state::{AppState, GameState};
+use bevy::app::{EventReader, Events};
+use bevy::ecs::{Local, Query, Res, ResMut};
+use bevy::ui::widget::Text;
+use simple_pong_bevy::events::MatchWon;
+use simple_pong_bevy::rules::MatchMode;
+use simple_pong_bevy::simulation::{Side, Simulation};
+
+/// Ends the match once it is won and keeps the scoreboard's status line (time left, match point,
+/// sudden death) up to date.
+pub fn match_rules_system(
+    mut event_reader: Local<EventReader<MatchWon>>,
+    wins: Res<Events<MatchWon>>,
+    mut state: ResMut<GameState>,
+    simulation: Res<Simulation>,
+    score_text: Res<ScoreText>,
+    mut text_query: Query<&mut Text>,
+) {
+    let (left, right) = (simulation.score_left, simulation.score_right);
+    let rules = &simulation.rules;
+    let elapsed = simulation.elapsed();
+
+    let won = event_reader.iter(&wins).last().is_some();
+    if won && state.is(AppState::Playing) {
+        state.set(AppState::GameOver);
+    }
+
+    let mut status = Vec::new();
+    if let Some(remaining) = rules.remaining_time(elapsed) {
+        let seconds = remaining.ceil() as i32;
+        status.push(format!("{}:{:02}", seconds / 60, seconds % 60));
+    }
+    if simulation.winner.is_none() {
+        if rules.is_time_up(elapsed) {
+            status.push("SUDDEN DEATH".to_string());
+        } else if rules.mode != MatchMode::FourPlayers {
+            if rules.is_match_point(Side::Left, left, right, elapsed) {
+                status.push("MATCH POINT LEFT".to_string());
+            }
+            if rules.is_match_point(Side::Right, left, right, elapsed) {
+                status.push("MATCH POINT RIGHT".to_string());
+            }
+        }
+    }
+
+    if let Ok(mut text) = text_query.get_mut(score_text.status) {
+        let value = status.join("  ");
+        if text.value != value {
+            text.value = value;
+        }
+    }
+}
@@ -1,11 +1,29 @@
 pub use self::paddle::paddle_system;
 mod paddle;
 
-pub use self::move_balls::move_balls_system;
-mod move_balls;
+pub use self::gamepad::gamepad_connection_system;
+mod gamepad;
 
-pub use self::bounce::bounce_system;
-mod bounce;
+pub use self::pointer::pointer_system;
+mod pointer;
+
+pub use self::network::network_system;
+mod network;
+
+pub use self::simulation::simulation_system;
+mod simulation;
+
+pub use self::interpolate::{interpolate_balls_system, interpolate_paddles_system};
+mod interpolate;
+
+pub use self::game_events::game_events_system;
+mod game_events;
 
 pub use self::winner::winner_system;
 mod winner;
+
+pub use self::match_rules::match_rules_system;
+mod match_rules;
+
+pub use self::broadcast::broadcast_system;
+mod broadcast;
@@ -0,0 +1,78 @@
+use crate::game::NetworkMatch;
+use crate::state::{AppState, GameState};
+use bevy::core::Time;
+use bevy::ecs::{Res, ResMut};
+use simple_pong_bevy::simulation::{PaddleInput, Simulation, TICK_DELTA};
+
+/// Seconds of play without a packet from the other peer or the server before an online match
+/// is given up.
+const TIMEOUT_SECONDS: f32 = 10.0;
+
+/// Talks to the other peer or the server every frame, in every state, so nobody times out
+/// while a player is paused or counting down. Shows the session's served arena during the
+/// countdown, takes the players and spectators of a server on to its next match and gives up
+/// the match once the other side went silent.
+pub fn network_system(
+    mut state: ResMut<GameState>,
+    mut network: ResMut<NetworkMatch>,
+    mut simulation: ResMut<Simulation>,
+    time: Res<Time>,
+) {
+    if state.just_entered(AppState::MainMenu) {
+        network.handshake = None;
+        network.session = None;
+        if let Some(mut server) = network.server.take() {
+            if let Err(error) = server.disconnect() {
+                eprintln!("Network error: {}", error);
+            }
+        }
+    }
+
+    let lost = if let Some(session) = &mut network.session {
+        let mut result = session.poll();
+        if result.is_ok() && !state.is(AppState::Playing) {
+            result = session.send_inputs();
+        }
+        if result.is_ok() && state.is(AppState::Countdown) {
+            *simulation = session.simulation().clone();
+        }
+        match result {
+            Ok(()) => session.ticks_since_received() as f32 * TICK_DELTA > TIMEOUT_SECONDS,
+            Err(error) => {
+                eprintln!("Network error: {}", error);
+                true
+            }
+        }
+    } else if let Some(server) = &mut network.server {
+        // While playing the simulation system sends the paddle's input.
+        let mut result = Ok(());
+        if !state.is(AppState::Playing) && !state.is(AppState::Connecting) {
+            result = server.update(time.delta_seconds(), PaddleInput::default());
+        }
+        // The server goes on with a rematch, it starts here once the view gets to it.
+        if state.is(AppState::GameOver) {
+            server
+                .snapshots
+                .show(time.delta_seconds() / TICK_DELTA, &mut simulation);
+            if simulation.winner.is_none() {
+                state.set(AppState::Playing);
+            }
+        }
+        match result {
+            Ok(_) => server.silence() > TIMEOUT_SECONDS,
+            Err(error) => {
+                eprintln!("Network error: {}", error);
+                true
+            }
+        }
+    } else {
+        false
+    };
+
+    if lost {
+        eprintln!("Lost the connection");
+        network.session = None;
+        network.server = None;
+        state.set(AppState::MainMenu);
+    }
+}
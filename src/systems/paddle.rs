@@ -1,44 +1,36 @@
-use crate::game::{Paddle, Side, ARENA_HEIGHT, PADDLE_HEIGHT};
-use bevy::core::Time;
-use bevy::ecs::{Query, Res};
+use crate::game::PaddleInputs;
+use crate::input::{GamepadAssignments, InputMap};
+use bevy::ecs::{Res, ResMut};
+use bevy::input::gamepad::GamepadAxis;
 use bevy::input::keyboard::KeyCode;
-use bevy::input::Input;
-use bevy::prelude::Transform;
+use bevy::input::{Axis, Input};
+use simple_pong_bevy::simulation::{PaddleInput, Side, Simulation};
 
+/// Reads the keyboard and gamepads into `PaddleInputs`, the simulation applies them on its next
+/// ticks. Held keys win over the stick, which moves the paddle proportionally to its deflection.
+/// Each player uses the controls of the side with their index, see `InputMap`.
 pub fn paddle_system(
-    time: Res<Time>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Paddle, &mut Transform)>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    input_map: Res<InputMap>,
+    assignments: Res<GamepadAssignments>,
+    simulation: Res<Simulation>,
+    mut inputs: ResMut<PaddleInputs>,
 ) {
-    for (paddle, mut transform) in query.iter_mut() {
-        let movement = match paddle.side {
-            Side::Left => {
-                let mut dir = 0.0;
-                if keyboard_input.pressed(KeyCode::W) {
-                    dir += 1.0
-                }
-                if keyboard_input.pressed(KeyCode::S) {
-                    dir -= 1.0
-                }
-                dir
+    for (player, controls) in Side::ALL.iter().enumerate() {
+        let mut movement = input_map.movement(*controls, &keyboard_input);
+        if movement == 0.0 {
+            if let Some(gamepad) = assignments.get(*controls) {
+                // The stick follows the paddle, the front lanes of doubles move up and down.
+                let side = simulation
+                    .paddles
+                    .get(player)
+                    .map(|paddle| paddle.side)
+                    .unwrap_or(*controls);
+                movement = input_map.stick(gamepad, side, &gamepad_axes);
             }
-            Side::Right => {
-                let mut dir = 0.0;
-                if keyboard_input.pressed(KeyCode::Up) {
-                    dir += 1.0
-                }
-                if keyboard_input.pressed(KeyCode::Down) {
-                    dir -= 1.0
-                }
-                dir
-            }
-        };
+        }
 
-        let translation = &mut transform.translation;
-        translation.y += time.delta_seconds() * movement * 120.0;
-        translation.y = translation
-            .y
-            .min(ARENA_HEIGHT - PADDLE_HEIGHT * 0.5)
-            .max(PADDLE_HEIGHT * 0.5);
+        inputs.0[player] = PaddleInput::from_axis(movement);
     }
 }
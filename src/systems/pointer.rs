@@ -0,0 +1,42 @@
+use crate::camera::{screen_to_world, VirtualScreen};
+use crate::cli::Controller;
+use crate::game::{MatchSetup, PointerTargets};
+use bevy::ecs::{Query, Res, ResMut};
+use bevy::input::touch::Touches;
+use bevy::prelude::Transform;
+use bevy::render::camera::Camera;
+use bevy::window::Windows;
+
+/// Turns the cursor or the first touch point into the height that pointer controlled paddles
+/// move towards. A touch wins over the cursor.
+pub fn pointer_system(
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    setup: Res<MatchSetup>,
+    mut targets: ResMut<PointerTargets>,
+    camera_query: Query<(&Camera, &VirtualScreen, &Transform)>,
+) {
+    let mut target_y = None;
+    for (camera, virtual_screen, transform) in camera_query.iter() {
+        let window = match windows.get(camera.window) {
+            Some(window) => window,
+            None => continue,
+        };
+        let position = touches
+            .iter()
+            .next()
+            .map(|touch| touch.position)
+            .or_else(|| window.cursor_position());
+        if let Some(position) = position {
+            target_y = Some(screen_to_world(virtual_screen, window, transform, position).y);
+        }
+    }
+
+    // Only the left and right back paddle move up and down with the pointer.
+    for (player, target) in targets.0.iter_mut().enumerate() {
+        *target = match setup.controllers[player] {
+            Controller::Pointer => target_y,
+            _ => None,
+        };
+    }
+}
@@ -0,0 +1,159 @@
+use crate::game::{MatchReplay, NetworkMatch, Paddle, PaddleInputs, PointerTargets};
+use crate::state::{AppState, GameState};
+use bevy::app::Events;
+use bevy::core::Time;
+use bevy::ecs::{Query, Res, ResMut};
+use simple_pong_bevy::ai::AiController;
+use simple_pong_bevy::net::{RollbackSession, ServerConnection, Transport, UdpTransport};
+use simple_pong_bevy::simulation::{PaddleInput, SimEvent, Simulation, TickClock, TICK_DELTA};
+
+/// Advances the simulation by as many fixed ticks as fit into the frame time and publishes what
+/// happened as `SimEvent`s. Computer and pointer controlled paddles decide on their input every
+/// tick, so they never overshoot their target. The inputs of every tick go into the match's
+/// replay, or come from it during playback. Online matches are run by the network session or
+/// a dedicated server.
+pub fn simulation_system(
+    state: Res<GameState>,
+    time: Res<Time>,
+    inputs: Res<PaddleInputs>,
+    pointer_targets: Res<PointerTargets>,
+    mut clock: ResMut<TickClock>,
+    mut match_replay: ResMut<MatchReplay>,
+    mut network: ResMut<NetworkMatch>,
+    mut simulation: ResMut<Simulation>,
+    mut events: ResMut<Events<SimEvent>>,
+    mut ai_query: Query<(&Paddle, &mut AiController)>,
+) {
+    if !state.is(AppState::Playing) {
+        return;
+    }
+    if let Some(session) = &mut network.session {
+        let ticks = clock.advance(time.delta_seconds());
+        advance_session(
+            session,
+            ticks,
+            &inputs,
+            &pointer_targets,
+            &mut match_replay,
+            &mut simulation,
+            &mut events,
+        );
+        return;
+    }
+    if let Some(server) = &mut network.server {
+        follow_server(
+            server,
+            time.delta_seconds(),
+            &inputs,
+            &pointer_targets,
+            &mut clock,
+            &mut simulation,
+            &mut events,
+        );
+        return;
+    }
+
+    for _ in 0..clock.advance(time.delta_seconds()) {
+        if simulation.winner.is_some() {
+            break;
+        }
+
+        let tick_inputs = if match_replay.playback {
+            match match_replay.replay.inputs.get(simulation.tick as usize) {
+                Some(tick_inputs) => *tick_inputs,
+                None => break,
+            }
+        } else {
+            let mut tick_inputs = inputs.0;
+            for (index, target_y) in pointer_targets.0.iter().enumerate() {
+                if let Some(target_y) = target_y {
+                    let y = simulation.paddles[index].position[1];
+                    tick_inputs[index] = PaddleInput::towards(y, *target_y);
+                }
+            }
+            for (paddle, mut ai) in ai_query.iter_mut() {
+                tick_inputs[paddle.player] = ai.input(&simulation, paddle.player);
+            }
+            match_replay.replay.inputs.push(tick_inputs);
+            tick_inputs
+        };
+
+        for event in simulation.step(&tick_inputs) {
+            events.send(event);
+        }
+    }
+}
+
+/// Runs the local paddle of an online match. The match only ends once both peers agree on the
+/// winner, a predicted winner could still be rolled back.
+fn advance_session(
+    session: &mut RollbackSession<UdpTransport>,
+    ticks: u32,
+    inputs: &PaddleInputs,
+    pointer_targets: &PointerTargets,
+    match_replay: &mut MatchReplay,
+    simulation: &mut Simulation,
+    events: &mut Events<SimEvent>,
+) {
+    let index = session.local_side().index();
+    for _ in 0..ticks {
+        let input = match pointer_targets.0[index] {
+            Some(target_y) => {
+                PaddleInput::towards(session.simulation().paddles[index].position[1], target_y)
+            }
+            None => inputs.0[index],
+        };
+        match session.advance(input) {
+            Ok(Some(tick_events)) => {
+                for event in tick_events {
+                    events.send(event);
+                }
+            }
+            // Waiting for the other peer to catch up.
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("Network error: {}", error);
+                break;
+            }
+        }
+    }
+
+    *simulation = session.simulation().clone();
+    simulation.winner = session.confirmed().winner;
+    if simulation.winner.is_some() {
+        match_replay.replay.inputs = session.confirmed_inputs();
+    }
+}
+
+/// Sends the local paddle's input to a dedicated server and shows its match, a little in the
+/// past so the view can move smoothly between the states it sends. The match's events are
+/// published once the view reaches them.
+fn follow_server(
+    server: &mut ServerConnection<Box<dyn Transport>>,
+    seconds: f32,
+    inputs: &PaddleInputs,
+    pointer_targets: &PointerTargets,
+    clock: &mut TickClock,
+    simulation: &mut Simulation,
+    events: &mut Events<SimEvent>,
+) {
+    let input = match server.side() {
+        Some(side) => match pointer_targets.0[side.index()] {
+            Some(target_y) => {
+                PaddleInput::towards(simulation.paddles[side.index()].position[1], target_y)
+            }
+            None => inputs.0[side.index()],
+        },
+        None => PaddleInput::default(),
+    };
+    if let Err(error) = server.update(seconds, input) {
+        eprintln!("Network error: {}", error);
+    }
+
+    if let Some(alpha) = server.snapshots.show(seconds / TICK_DELTA, simulation) {
+        clock.alpha = alpha;
+    }
+    for event in server.snapshots.take_events() {
+        events.send(event);
+    }
+}
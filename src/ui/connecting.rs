@@ -0,0 +1,138 @@
+use crate::cli::{Controller, LaunchOptions, NetworkRole};
+use crate::game::{MatchReplay, MatchSetup, NetworkMatch, UiAssets};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::core::Time;
+use bevy::ecs::{Commands, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use simple_pong_bevy::net::{
+    Handshake, ServerConnection, TcpTransport, UdpTransport, INTERPOLATION_DELAY,
+};
+use simple_pong_bevy::replay::Replay;
+use simple_pong_bevy::simulation::{PaddleInput, Simulation, MAX_PLAYERS};
+use std::io;
+
+/// Opens the socket of an online match and waits for the other peer or the server. Against
+/// another peer both players then get the host's settings and a countdown, a server or a
+/// broadcast counts down itself.
+pub fn connecting_system(
+    commands: &mut Commands,
+    mut state: ResMut<GameState>,
+    mut network: ResMut<NetworkMatch>,
+    mut simulation: ResMut<Simulation>,
+    mut setup: ResMut<MatchSetup>,
+    mut match_replay: ResMut<MatchReplay>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    options: Res<LaunchOptions>,
+    ui_assets: Res<UiAssets>,
+) {
+    if state.just_entered(AppState::Connecting) {
+        // A match from the lobby comes with its handshake.
+        let waiting = match options.network {
+            _ if network.handshake.is_some() => "STARTING THE MATCH".to_string(),
+            Some(role) => match connect(role, &options, &mut network) {
+                Ok(waiting) => waiting,
+                Err(error) => {
+                    eprintln!("Could not open a network connection: {}", error);
+                    state.set(AppState::MainMenu);
+                    return;
+                }
+            },
+            None => {
+                state.set(AppState::MainMenu);
+                return;
+            }
+        };
+        spawn_text_screen(
+            commands,
+            &ui_assets,
+            &[AppState::Connecting],
+            &[(waiting, 30.0), ("ESC - CANCEL".to_string(), 20.0)],
+        );
+    }
+    if !state.is(AppState::Connecting) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu);
+        return;
+    }
+
+    // Both paddles are played by people, one of them on another machine.
+    setup.controllers = [Controller::Keyboard; MAX_PLAYERS];
+    match_replay.playback = false;
+
+    if let Some(handshake) = &mut network.handshake {
+        match handshake.poll() {
+            Ok(true) => {
+                let session = network.handshake.take().unwrap().into_session();
+                *simulation = session.simulation().clone();
+                network.session = Some(session);
+                state.set(AppState::Countdown);
+            }
+            Ok(false) => {}
+            Err(error) => {
+                eprintln!("Network error: {}", error);
+                state.set(AppState::MainMenu);
+            }
+        }
+    } else if let Some(server) = &mut network.server {
+        if let Err(error) = server.update(time.delta_seconds(), PaddleInput::default()) {
+            eprintln!("Network error: {}", error);
+            state.set(AppState::MainMenu);
+        } else if let Some((rules, speed_up)) = server.settings() {
+            *simulation = Simulation::new(rules.clone(), speed_up.clone());
+            match_replay.replay = Replay::new(0, rules.clone(), speed_up.clone());
+            state.set(AppState::Playing);
+        }
+    }
+}
+
+/// Starts talking to the other peer or the server, returns what to show while waiting.
+fn connect(
+    role: NetworkRole,
+    options: &LaunchOptions,
+    network: &mut NetworkMatch,
+) -> io::Result<String> {
+    Ok(match role {
+        NetworkRole::Host(port) => {
+            network.handshake = Some(Handshake::host(
+                UdpTransport::listen(port)?,
+                options.rules.clone(),
+                options.speed_up.clone(),
+            ));
+            format!("WAITING FOR A PLAYER ON PORT {}", port)
+        }
+        NetworkRole::Join(address) => {
+            network.handshake = Some(Handshake::join(UdpTransport::connect(address)?));
+            format!("CONNECTING TO {}", address)
+        }
+        NetworkRole::Server(address) => {
+            network.server = Some(ServerConnection::new(
+                Box::new(UdpTransport::connect(address)?),
+                false,
+                INTERPOLATION_DELAY,
+            ));
+            format!("CONNECTING TO SERVER {}", address)
+        }
+        NetworkRole::Spectate(address) => {
+            network.server = Some(ServerConnection::new(
+                Box::new(UdpTransport::connect(address)?),
+                true,
+                options.spectator_delay,
+            ));
+            format!("CONNECTING TO SERVER {}", address)
+        }
+        NetworkRole::Watch(address) => {
+            network.server = Some(ServerConnection::new(
+                Box::new(TcpTransport::connect(address)?),
+                true,
+                options.spectator_delay,
+            ));
+            format!("WATCHING {}", address)
+        }
+    })
+}
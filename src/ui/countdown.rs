@@ -0,0 +1,85 @@
+use crate::game::{MatchReplay, Paddle, ScoreText, UiAssets};
+use crate::input::{Action, GamepadAssignments, InputMap};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::core::Time;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::gamepad::GamepadButton;
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+use simple_pong_bevy::replay::Replay;
+use simple_pong_bevy::simulation::Simulation;
+use simple_pong_bevy::utils::Rng;
+
+const COUNTDOWN_SECONDS: f32 = 3.0;
+
+#[derive(Default)]
+pub struct CountdownState {
+    remaining: f32,
+    text: Option<Entity>,
+}
+
+/// Resets the arena for a new match and counts down before the ball is served.
+pub fn countdown_system(
+    commands: &mut Commands,
+    mut countdown: Local<CountdownState>,
+    mut state: ResMut<GameState>,
+    mut simulation: ResMut<Simulation>,
+    mut match_replay: ResMut<MatchReplay>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    input_map: Res<InputMap>,
+    assignments: Res<GamepadAssignments>,
+    score_text: Res<ScoreText>,
+    ui_assets: Res<UiAssets>,
+    paddle_query: Query<(Entity, &Paddle)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if state.just_entered(AppState::Countdown) {
+        if match_replay.playback {
+            *simulation = match_replay.replay.simulation();
+        } else {
+            simulation.reset();
+            let seed = Rng::from_time().next_u64();
+            match_replay.replay =
+                Replay::new(seed, simulation.rules.clone(), simulation.speed_up.clone());
+        }
+        score_text.show(&simulation, &mut text_query);
+
+        // The interpolation spawns the paddles of the new layout, with their computer players.
+        for (entity, _) in paddle_query.iter() {
+            commands.despawn(entity);
+        }
+
+        countdown.remaining = COUNTDOWN_SECONDS;
+        countdown.text = spawn_text_screen(
+            commands,
+            &ui_assets,
+            &[AppState::Countdown],
+            &[(countdown_label(countdown.remaining), 80.0)],
+        )
+        .first()
+        .copied();
+    }
+    if !state.is(AppState::Countdown) {
+        return;
+    }
+
+    countdown.remaining -= time.delta_seconds();
+    let serve = input_map.just_pressed(Action::Serve, &keyboard_input)
+        || assignments.just_pressed(Action::Serve, &gamepad_buttons);
+    if countdown.remaining <= 0.0 || serve {
+        state.set(AppState::Playing);
+    } else if let Some(mut text) = countdown
+        .text
+        .and_then(|entity| text_query.get_mut(entity).ok())
+    {
+        text.value = countdown_label(countdown.remaining);
+    }
+}
+
+fn countdown_label(remaining: f32) -> String {
+    (remaining.ceil() as i32).to_string()
+}
@@ -0,0 +1,68 @@
+use crate::game::{MatchReplay, NetworkMatch, UiAssets};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::ecs::{Commands, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use simple_pong_bevy::rules::MatchMode;
+use simple_pong_bevy::simulation::Simulation;
+use simple_pong_bevy::utils::config_path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn game_over_system(
+    commands: &mut Commands,
+    mut state: ResMut<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    simulation: Res<Simulation>,
+    match_replay: Res<MatchReplay>,
+    network: Res<NetworkMatch>,
+    ui_assets: Res<UiAssets>,
+) {
+    if state.just_entered(AppState::GameOver) {
+        if match_replay.save && !match_replay.playback && !match_replay.replay.inputs.is_empty() {
+            save_replay(&match_replay);
+        }
+
+        let winner = match simulation.winner {
+            Some(side) if simulation.rules.mode == MatchMode::Doubles => {
+                format!("{:?} TEAM WINS", side).to_uppercase()
+            }
+            Some(side) => format!("{:?} PLAYER WINS", side).to_uppercase(),
+            None => "GAME OVER".to_string(),
+        };
+        let mut lines = vec![(winner, 50.0)];
+        // A rematch online would need a new handshake, the players reconnect instead.
+        if !network.is_online() {
+            lines.push(("R - REMATCH".to_string(), 25.0));
+        }
+        // Matches on a dedicated server are not recorded here.
+        if !match_replay.replay.inputs.is_empty() {
+            lines.push(("V - WATCH REPLAY".to_string(), 25.0));
+        }
+        lines.push(("SPACE - MAIN MENU".to_string(), 25.0));
+        spawn_text_screen(commands, &ui_assets, &[AppState::GameOver], &lines);
+    }
+    if !state.is(AppState::GameOver) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::R) && !network.is_online() {
+        state.set(AppState::Countdown);
+    } else if keyboard_input.just_pressed(KeyCode::V) && !match_replay.replay.inputs.is_empty() {
+        state.set(AppState::ReplayViewer);
+    } else if keyboard_input.just_pressed(KeyCode::Space) {
+        state.set(AppState::MainMenu);
+    }
+}
+
+fn save_replay(match_replay: &MatchReplay) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = config_path(&format!("replays/match-{}.replay", timestamp));
+    match match_replay.replay.save(&path) {
+        Ok(()) => eprintln!("Saved replay to {}", path.display()),
+        Err(error) => eprintln!("Could not save replay to {}: {}", path.display(), error),
+    }
+}
@@ -0,0 +1,255 @@
+use crate::cli::LaunchOptions;
+use crate::game::{NetworkMatch, UiAssets};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::app::{EventReader, Events};
+use bevy::core::Time;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+use bevy::window::ReceivedCharacter;
+use simple_pong_bevy::net::{Lobby, LobbyFinder, LobbyListing, MAX_CHAT_LENGTH};
+use simple_pong_bevy::rules::{MatchMode, MatchRules};
+
+/// Lines between the title and the help line, for the found lobbies or the lobby's members
+/// and chat.
+const BODY_LINES: usize = 9;
+const CHAT_LINES: usize = 4;
+
+#[derive(Default)]
+pub struct LobbyScreen {
+    /// Looking for lobbies while not in one.
+    finder: Option<LobbyFinder>,
+    lobby: Option<Lobby>,
+    /// Index into the found lobbies.
+    selected: usize,
+    /// The chat line being typed.
+    typed: String,
+    body_texts: Vec<Entity>,
+    help_text: Option<Entity>,
+}
+
+/// Lists the lobbies on the local network to join one, or hosts a new one with the launch
+/// settings. In a lobby both players chat and get ready, then the match starts like any other
+/// match between two peers.
+pub fn lobby_system(
+    commands: &mut Commands,
+    mut screen: Local<LobbyScreen>,
+    mut character_reader: Local<EventReader<ReceivedCharacter>>,
+    mut state: ResMut<GameState>,
+    mut network: ResMut<NetworkMatch>,
+    characters: Res<Events<ReceivedCharacter>>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    options: Res<LaunchOptions>,
+    ui_assets: Res<UiAssets>,
+    mut text_query: Query<&mut Text>,
+) {
+    let typed: Vec<char> = character_reader
+        .iter(&characters)
+        .map(|character| character.char)
+        .filter(|character| !character.is_control())
+        .collect();
+
+    if state.just_entered(AppState::Lobby) {
+        let mut lines = vec![("LAN LOBBY".to_string(), 40.0)];
+        lines.extend((0..=BODY_LINES).map(|_| (String::new(), 20.0)));
+        let entities = spawn_text_screen(commands, &ui_assets, &[AppState::Lobby], &lines);
+        screen.body_texts = entities[1..=BODY_LINES].to_vec();
+        screen.help_text = entities.get(BODY_LINES + 1).copied();
+        screen.selected = 0;
+        screen.typed.clear();
+        screen.finder = None;
+    }
+    if !state.is(AppState::Lobby) {
+        if let Some(mut lobby) = screen.lobby.take() {
+            if let Err(error) = lobby.leave() {
+                eprintln!("Network error: {}", error);
+            }
+        }
+        screen.finder = None;
+        return;
+    }
+
+    let seconds = time.delta_seconds();
+    let screen = &mut *screen;
+    let (body, help) = if let Some(lobby) = &mut screen.lobby {
+        for character in typed {
+            if screen.typed.len() + character.len_utf8() <= MAX_CHAT_LENGTH {
+                screen.typed.push(character);
+            }
+        }
+        if keyboard_input.just_pressed(KeyCode::Back) {
+            screen.typed.pop();
+        }
+        if keyboard_input.just_pressed(KeyCode::Return) {
+            lobby.say(&screen.typed);
+            screen.typed.clear();
+        }
+        if keyboard_input.just_pressed(KeyCode::Tab) {
+            lobby.set_ready(!lobby.is_ready());
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Escape) {
+            if let Err(error) = lobby.leave() {
+                eprintln!("Network error: {}", error);
+            }
+            screen.lobby = None;
+            return;
+        }
+        match lobby.update(seconds) {
+            Ok(true) => {
+                match screen.lobby.take().unwrap().into_handshake() {
+                    Ok(handshake) => {
+                        network.handshake = Some(handshake);
+                        state.set(AppState::Connecting);
+                    }
+                    Err(error) => eprintln!("Network error: {}", error),
+                }
+                return;
+            }
+            Ok(false) => {}
+            Err(error) => {
+                eprintln!("Left the lobby: {}", error);
+                screen.lobby = None;
+                return;
+            }
+        }
+        (
+            lobby_lines(lobby, &screen.typed),
+            "TYPE + ENTER - CHAT  TAB - READY  ESC - LEAVE",
+        )
+    } else {
+        if screen.finder.is_none() {
+            match LobbyFinder::new() {
+                Ok(finder) => screen.finder = Some(finder),
+                Err(error) => {
+                    eprintln!("Could not look for lobbies: {}", error);
+                    state.set(AppState::MainMenu);
+                    return;
+                }
+            }
+        }
+        let finder = screen.finder.as_mut().unwrap();
+        if let Err(error) = finder.update(seconds) {
+            eprintln!("Could not look for lobbies: {}", error);
+            state.set(AppState::MainMenu);
+            return;
+        }
+        let lobbies = finder.lobbies();
+        screen.selected = screen.selected.min(lobbies.len().saturating_sub(1));
+
+        if keyboard_input.just_pressed(KeyCode::Up) && !lobbies.is_empty() {
+            screen.selected = (screen.selected + lobbies.len() - 1) % lobbies.len();
+        } else if keyboard_input.just_pressed(KeyCode::Down) && !lobbies.is_empty() {
+            screen.selected = (screen.selected + 1) % lobbies.len();
+        } else if keyboard_input.just_pressed(KeyCode::Escape) {
+            state.set(AppState::MainMenu);
+            return;
+        }
+
+        let entered = if keyboard_input.just_pressed(KeyCode::Return) && !lobbies.is_empty() {
+            Some(Lobby::join(&options.name, &lobbies[screen.selected]))
+        } else if keyboard_input.just_pressed(KeyCode::H) {
+            // Online matches are for two players.
+            let rules = MatchRules {
+                mode: MatchMode::Singles,
+                ..options.rules.clone()
+            };
+            Some(Lobby::host(&options.name, rules, options.speed_up.clone()))
+        } else {
+            None
+        };
+        let body = listing_lines(lobbies, screen.selected);
+        match entered {
+            Some(Ok(lobby)) => {
+                screen.lobby = Some(lobby);
+                screen.finder = None;
+                screen.typed.clear();
+            }
+            Some(Err(error)) => eprintln!("Could not enter the lobby: {}", error),
+            None => {}
+        }
+        (body, "UP/DOWN - SELECT  ENTER - JOIN  H - HOST  ESC - BACK")
+    };
+
+    for (index, entity) in screen.body_texts.iter().enumerate() {
+        let value = body.get(index).cloned().unwrap_or_default();
+        set_text(&mut text_query, *entity, value);
+    }
+    if let Some(entity) = screen.help_text {
+        set_text(&mut text_query, entity, help.to_string());
+    }
+}
+
+fn listing_lines(lobbies: &[LobbyListing], selected: usize) -> Vec<String> {
+    if lobbies.is_empty() {
+        return vec!["LOOKING FOR LOBBIES ON THE LOCAL NETWORK...".to_string()];
+    }
+    lobbies
+        .iter()
+        .enumerate()
+        .take(BODY_LINES)
+        .map(|(index, lobby)| {
+            let names: Vec<&str> = lobby
+                .members
+                .iter()
+                .map(|member| member.name.as_str())
+                .collect();
+            format!(
+                "{} {} ({}/2) - {}",
+                if index == selected { ">" } else { " " },
+                names.join(", "),
+                lobby.members.len(),
+                rules_label(&lobby.rules)
+            )
+        })
+        .collect()
+}
+
+fn lobby_lines(lobby: &Lobby, typed: &str) -> Vec<String> {
+    let members = lobby.members();
+    let mut lines = vec![rules_label(lobby.rules())];
+    for member in &members {
+        lines.push(format!(
+            "{} - {}",
+            member.name,
+            if member.ready { "READY" } else { "NOT READY" }
+        ));
+    }
+    if members.len() < 2 {
+        lines.push("WAITING FOR A PLAYER...".to_string());
+    }
+    lines.push(String::new());
+
+    let chat = lobby.chat();
+    lines.extend(
+        chat[chat.len().saturating_sub(CHAT_LINES)..]
+            .iter()
+            .map(|line| format!("{}: {}", line.name, line.text)),
+    );
+    lines.resize(BODY_LINES - 1, String::new());
+    lines.push(format!("> {}_", typed));
+    lines
+}
+
+fn rules_label(rules: &MatchRules) -> String {
+    let mut label = format!("FIRST TO {}", rules.points_to_win);
+    if rules.win_by_two {
+        label.push_str(", WIN BY TWO");
+    }
+    if let Some(limit) = rules.time_limit {
+        let seconds = limit.ceil() as i32;
+        label.push_str(&format!(", {}:{:02}", seconds / 60, seconds % 60));
+    }
+    label
+}
+
+fn set_text(text_query: &mut Query<&mut Text>, entity: Entity, value: String) {
+    if let Ok(mut text) = text_query.get_mut(entity) {
+        if text.value != value {
+            text.value = value;
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::cli::Controller;
+use crate::game::{MatchReplay, MatchSetup, UiAssets};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::app::{AppExit, Events};
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+use simple_pong_bevy::ai::AiDifficulty;
+use simple_pong_bevy::rules::MatchMode;
+use simple_pong_bevy::simulation::Simulation;
+
+#[derive(Default)]
+pub struct MainMenuState {
+    difficulty_text: Option<Entity>,
+    left_text: Option<Entity>,
+}
+
+pub fn main_menu_system(
+    commands: &mut Commands,
+    mut menu: Local<MainMenuState>,
+    mut state: ResMut<GameState>,
+    mut setup: ResMut<MatchSetup>,
+    mut match_replay: ResMut<MatchReplay>,
+    mut simulation: ResMut<Simulation>,
+    mut app_exit_events: ResMut<Events<AppExit>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    ui_assets: Res<UiAssets>,
+    mut text_query: Query<&mut Text>,
+) {
+    if state.just_entered(AppState::MainMenu) {
+        let lines = spawn_text_screen(
+            commands,
+            &ui_assets,
+            &[AppState::MainMenu],
+            &[
+                ("SIMPLE PONG".to_string(), 60.0),
+                ("1 - PLAYER VS COMPUTER".to_string(), 25.0),
+                ("2 - PLAYER VS PLAYER".to_string(), 25.0),
+                ("3 - DOUBLES".to_string(), 25.0),
+                ("4 - FOUR PLAYERS".to_string(), 25.0),
+                (difficulty_label(setup.difficulty), 25.0),
+                (left_label(setup.controllers[0]), 25.0),
+                ("L - LAN LOBBY".to_string(), 25.0),
+                ("S - CONTROLS".to_string(), 25.0),
+                ("U - MUSIC".to_string(), 25.0),
+                ("V - VOLUME".to_string(), 25.0),
+                ("ESC - QUIT".to_string(), 25.0),
+            ],
+        );
+        menu.difficulty_text = lines.get(5).copied();
+        menu.left_text = lines.get(6).copied();
+    }
+    if !state.is(AppState::MainMenu) {
+        return;
+    }
+
+    // The left paddle is always played by a human, with the controls picked with `M`.
+    if setup.controllers[0] == Controller::Computer {
+        setup.controllers[0] = Controller::Keyboard;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Key1) {
+        match_replay.playback = false;
+        simulation.rules.mode = MatchMode::Singles;
+        setup.controllers[1] = Controller::Computer;
+        state.set(AppState::Countdown);
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        match_replay.playback = false;
+        simulation.rules.mode = MatchMode::Singles;
+        setup.controllers[1] = Controller::Keyboard;
+        state.set(AppState::Countdown);
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        // The front lane players keep the controllers from the command line.
+        match_replay.playback = false;
+        simulation.rules.mode = MatchMode::Doubles;
+        setup.controllers[1] = Controller::Keyboard;
+        state.set(AppState::Countdown);
+    } else if keyboard_input.just_pressed(KeyCode::Key4) {
+        // The top and bottom players keep the controllers from the command line.
+        match_replay.playback = false;
+        simulation.rules.mode = MatchMode::FourPlayers;
+        setup.controllers[1] = Controller::Keyboard;
+        state.set(AppState::Countdown);
+    } else if keyboard_input.just_pressed(KeyCode::D) {
+        let index = AiDifficulty::ALL
+            .iter()
+            .position(|difficulty| *difficulty == setup.difficulty)
+            .unwrap_or(0);
+        setup.difficulty = AiDifficulty::ALL[(index + 1) % AiDifficulty::ALL.len()];
+
+        if let Some(mut text) = menu
+            .difficulty_text
+            .and_then(|entity| text_query.get_mut(entity).ok())
+        {
+            text.value = difficulty_label(setup.difficulty);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::M) {
+        setup.controllers[0] = match setup.controllers[0] {
+            Controller::Pointer => Controller::Keyboard,
+            _ => Controller::Pointer,
+        };
+
+        if let Some(mut text) = menu
+            .left_text
+            .and_then(|entity| text_query.get_mut(entity).ok())
+        {
+            text.value = left_label(setup.controllers[0]);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::L) {
+        state.set(AppState::Lobby);
+    } else if keyboard_input.just_pressed(KeyCode::S) {
+        state.set(AppState::Settings);
+    } else if keyboard_input.just_pressed(KeyCode::U) {
+        state.set(AppState::Music);
+    } else if keyboard_input.just_pressed(KeyCode::V) {
+        state.set(AppState::Volume);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_exit_events.send(AppExit);
+    }
+}
+
+fn left_label(controller: Controller) -> String {
+    match controller {
+        Controller::Pointer => "M - LEFT PADDLE: MOUSE".to_string(),
+        _ => "M - LEFT PADDLE: KEYS".to_string(),
+    }
+}
+
+fn difficulty_label(difficulty: AiDifficulty) -> String {
+    format!("D - DIFFICULTY: {}", difficulty.name().to_uppercase())
+}
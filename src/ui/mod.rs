@@ -0,0 +1,93 @@
+pub use self::connecting::connecting_system;
+mod connecting;
+
+pub use self::countdown::countdown_system;
+mod countdown;
+
+pub use self::game_over::game_over_system;
+mod game_over;
+
+pub use self::lobby::lobby_system;
+mod lobby;
+
+pub use self::main_menu::main_menu_system;
+mod main_menu;
+
+pub use self::music::music_menu_system;
+mod music;
+
+pub use self::pause::pause_system;
+mod pause;
+
+pub use self::replay_viewer::replay_viewer_system;
+mod replay_viewer;
+
+pub use self::settings::settings_system;
+mod settings;
+
+pub use self::volume::volume_system;
+mod volume;
+
+use crate::game::UiAssets;
+use crate::state::{AppState, StateScoped};
+use bevy::ecs::{Commands, Entity};
+use bevy::math::Size;
+use bevy::prelude::{BuildChildren, HorizontalAlign, NodeBundle, TextBundle, VerticalAlign};
+use bevy::render::color::Color;
+use bevy::text::{TextAlignment, TextStyle};
+use bevy::ui::widget::Text;
+use bevy::ui::{AlignItems, FlexDirection, JustifyContent, Style, Val};
+
+/// Spawns one centered text line per entry, the whole screen lives as long as `scope` allows.
+/// Returns the text entities so screens can update single lines later on.
+pub fn spawn_text_screen(
+    commands: &mut Commands,
+    ui_assets: &UiAssets,
+    scope: &'static [AppState],
+    lines: &[(String, f32)],
+) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                // UI coordinates grow upwards, reverse so the first line ends up on top.
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: ui_assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateScoped(scope))
+        .with_children(|parent| {
+            for (line, font_size) in lines {
+                let entity = parent
+                    .spawn(TextBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(600.0), Val::Px(font_size * 1.5)),
+                            ..Default::default()
+                        },
+                        text: Text {
+                            value: line.clone(),
+                            font: ui_assets.font.clone(),
+                            style: TextStyle {
+                                font_size: *font_size,
+                                color: Color::rgb(1.0, 1.0, 1.0),
+                                alignment: TextAlignment {
+                                    vertical: VerticalAlign::Center,
+                                    horizontal: HorizontalAlign::Center,
+                                },
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .current_entity();
+                entities.extend(entity);
+            }
+        });
+    entities
+}
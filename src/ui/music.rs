@@ -0,0 +1,92 @@
+use crate::audio::Music;
+use crate::game::UiAssets;
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+
+#[derive(Default)]
+pub struct MusicScreen {
+    /// The lines that change with the music, see `music_lines`.
+    texts: Vec<Entity>,
+}
+
+/// Shows the track that is playing and controls the playlist.
+pub fn music_menu_system(
+    commands: &mut Commands,
+    mut screen: Local<MusicScreen>,
+    mut state: ResMut<GameState>,
+    mut music: ResMut<Music>,
+    keyboard_input: Res<Input<KeyCode>>,
+    ui_assets: Res<UiAssets>,
+    mut text_query: Query<&mut Text>,
+) {
+    if state.just_entered(AppState::Music) {
+        let mut lines = vec![("MUSIC".to_string(), 50.0)];
+        lines.extend(music_lines(&music).into_iter().map(|line| (line, 25.0)));
+        lines.push(("N - NEXT TRACK  B - PREVIOUS TRACK".to_string(), 20.0));
+        lines.push(("ESC - BACK".to_string(), 20.0));
+        let entities = spawn_text_screen(commands, &ui_assets, &[AppState::Music], &lines);
+        screen.texts = entities[1..lines.len() - 2].to_vec();
+    }
+    if !state.is(AppState::Music) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::N) {
+        music.skip();
+    } else if keyboard_input.just_pressed(KeyCode::B) {
+        music.back();
+    } else if keyboard_input.just_pressed(KeyCode::Space) {
+        music.toggle_pause();
+    } else if keyboard_input.just_pressed(KeyCode::S) {
+        let shuffle = !music.playlist.is_shuffled();
+        music.playlist.set_shuffle(shuffle);
+    } else if keyboard_input.just_pressed(KeyCode::R) {
+        music.playlist.repeat = music.playlist.repeat.next();
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu);
+        return;
+    }
+
+    // Tracks also change by themselves, so the lines are refreshed every frame.
+    for (entity, value) in screen.texts.iter().zip(music_lines(&music)) {
+        if let Ok(mut text) = text_query.get_mut(*entity) {
+            if text.value != value {
+                text.value = value;
+            }
+        }
+    }
+}
+
+fn music_lines(music: &Music) -> Vec<String> {
+    vec![
+        match music.now_playing() {
+            Some(track) => track.title.to_uppercase(),
+            None => "NOTHING PLAYING".to_string(),
+        },
+        match music.now_playing() {
+            Some(track) => format!("BY {} ({})", track.artist, track.license).to_uppercase(),
+            None => String::new(),
+        },
+        if music.is_paused() {
+            "SPACE - PLAY".to_string()
+        } else {
+            "SPACE - PAUSE".to_string()
+        },
+        format!(
+            "S - SHUFFLE: {}",
+            if music.playlist.is_shuffled() {
+                "ON"
+            } else {
+                "OFF"
+            }
+        ),
+        format!(
+            "R - REPEAT: {}",
+            music.playlist.repeat.name().to_uppercase()
+        ),
+    ]
+}
@@ -0,0 +1,49 @@
+use crate::game::UiAssets;
+use crate::input::{Action, GamepadAssignments, InputMap};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::ecs::{Commands, Res, ResMut};
+use bevy::input::gamepad::GamepadButton;
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+
+pub fn pause_system(
+    commands: &mut Commands,
+    mut state: ResMut<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    input_map: Res<InputMap>,
+    assignments: Res<GamepadAssignments>,
+    ui_assets: Res<UiAssets>,
+) {
+    let toggle = input_map.just_pressed(Action::Pause, &keyboard_input)
+        || assignments.just_pressed(Action::Pause, &gamepad_buttons);
+
+    match state.current() {
+        AppState::Playing if toggle => state.set(AppState::Paused),
+        AppState::Paused => {
+            if state.just_entered(AppState::Paused) {
+                spawn_text_screen(
+                    commands,
+                    &ui_assets,
+                    &[AppState::Paused],
+                    &[
+                        ("PAUSED".to_string(), 60.0),
+                        (
+                            format!("{} - RESUME", input_map.labels(Action::Pause)),
+                            25.0,
+                        ),
+                        ("Q - MAIN MENU".to_string(), 25.0),
+                    ],
+                );
+            }
+
+            if toggle {
+                state.set(AppState::Playing);
+            } else if keyboard_input.just_pressed(KeyCode::Q) {
+                state.set(AppState::MainMenu);
+            }
+        }
+        _ => {}
+    }
+}
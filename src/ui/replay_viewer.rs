@@ -0,0 +1,270 @@
+use crate::game::{MatchReplay, ScoreText, UiAssets};
+use crate::state::{AppState, GameState, StateScoped};
+use bevy::core::Time;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::mouse::MouseButton;
+use bevy::input::Input;
+use bevy::math::{Rect, Size};
+use bevy::prelude::{BuildChildren, HorizontalAlign, NodeBundle, TextBundle, VerticalAlign};
+use bevy::render::color::Color;
+use bevy::text::{TextAlignment, TextStyle};
+use bevy::ui::widget::Text;
+use bevy::ui::{AlignItems, FlexDirection, JustifyContent, PositionType, Style, Val};
+use bevy::window::Windows;
+use simple_pong_bevy::replay::Snapshot;
+use simple_pong_bevy::simulation::{Simulation, TickClock, TICK_DELTA};
+
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+const SEEK_SECONDS: f32 = 5.0;
+// Height of the timeline at the bottom of the window, clicks below it seek.
+const TIMELINE_HEIGHT: f32 = 12.0;
+
+#[derive(Default)]
+pub struct ReplayViewerState {
+    snapshots: Vec<Snapshot>,
+    /// Current tick, fractional while playing slower than real time.
+    position: f32,
+    playing: bool,
+    speed_index: usize,
+    status_text: Option<Entity>,
+    progress: Option<Entity>,
+}
+
+/// Shows the last match again. Space plays and pauses, the arrow keys step single ticks and
+/// change the speed, page up/down and the number keys seek. Clicking the timeline jumps there.
+pub fn replay_viewer_system(
+    commands: &mut Commands,
+    mut viewer: Local<ReplayViewerState>,
+    mut state: ResMut<GameState>,
+    mut simulation: ResMut<Simulation>,
+    mut clock: ResMut<TickClock>,
+    match_replay: Res<MatchReplay>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    ui_assets: Res<UiAssets>,
+    score_text: Res<ScoreText>,
+    mut text_query: Query<&mut Text>,
+    mut style_query: Query<&mut Style>,
+) {
+    if state.just_entered(AppState::ReplayViewer) {
+        viewer.snapshots = match_replay.replay.snapshots();
+        viewer.position = 0.0;
+        viewer.playing = true;
+        viewer.speed_index = 2;
+        spawn_viewer_ui(commands, &mut viewer, &ui_assets);
+    }
+    if !state.is(AppState::ReplayViewer) {
+        return;
+    }
+
+    let last_tick = (viewer.snapshots.len() - 1) as f32;
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu);
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Space) {
+        // Playing from the end starts over.
+        if !viewer.playing && viewer.position >= last_tick {
+            viewer.position = 0.0;
+        }
+        viewer.playing = !viewer.playing;
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        viewer.playing = false;
+        viewer.position = viewer.position.floor() + 1.0;
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        viewer.playing = false;
+        viewer.position = viewer.position.ceil() - 1.0;
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        viewer.speed_index = (viewer.speed_index + 1).min(SPEEDS.len() - 1);
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        viewer.speed_index = viewer.speed_index.saturating_sub(1);
+    } else if keyboard_input.just_pressed(KeyCode::PageUp) {
+        viewer.position += SEEK_SECONDS / TICK_DELTA;
+    } else if keyboard_input.just_pressed(KeyCode::PageDown) {
+        viewer.position -= SEEK_SECONDS / TICK_DELTA;
+    } else if let Some(tenth) = NUMBER_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    {
+        viewer.position = last_tick * tenth as f32 / 10.0;
+    } else if mouse_input.just_pressed(MouseButton::Left) {
+        if let Some((window, cursor)) = windows
+            .get_primary()
+            .and_then(|window| window.cursor_position().map(|cursor| (window, cursor)))
+        {
+            if cursor.y <= TIMELINE_HEIGHT * 2.0 {
+                viewer.position = last_tick * cursor.x / window.width() as f32;
+            }
+        }
+    }
+
+    if viewer.playing {
+        viewer.position += time.delta_seconds() * SPEEDS[viewer.speed_index] / TICK_DELTA;
+    }
+    viewer.position = viewer.position.clamp(0.0, last_tick);
+    if viewer.position >= last_tick {
+        viewer.playing = false;
+    }
+
+    show_position(&viewer, &mut simulation, &mut clock);
+
+    score_text.show(&simulation, &mut text_query);
+    if let Some(mut text) = viewer
+        .status_text
+        .and_then(|entity| text_query.get_mut(entity).ok())
+    {
+        text.value = format!(
+            "{} {}X  {} / {}",
+            if viewer.playing { "PLAYING" } else { "PAUSED" },
+            SPEEDS[viewer.speed_index],
+            format_time(viewer.position),
+            format_time(last_tick)
+        );
+    }
+    if let Some(mut style) = viewer
+        .progress
+        .and_then(|entity| style_query.get_mut(entity).ok())
+    {
+        style.size.width = Val::Percent(100.0 * viewer.position / last_tick.max(1.0));
+    }
+}
+
+const NUMBER_KEYS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Puts the snapshots around the current position into the simulation, the interpolation
+/// systems then draw the paddles and balls in between them.
+fn show_position(viewer: &ReplayViewerState, simulation: &mut Simulation, clock: &mut TickClock) {
+    let from = &viewer.snapshots[viewer.position.floor() as usize];
+    let to = &viewer.snapshots[viewer.position.ceil() as usize];
+
+    from.show_until(to, simulation);
+    clock.alpha = viewer.position.fract();
+}
+
+fn format_time(tick: f32) -> String {
+    let seconds = (tick * TICK_DELTA) as i32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The status line and the timeline along the bottom of the window. Every point scored or life
+/// lost gets a marker on the timeline.
+fn spawn_viewer_ui(commands: &mut Commands, viewer: &mut ReplayViewerState, ui_assets: &UiAssets) {
+    let last_tick = (viewer.snapshots.len() - 1).max(1) as f32;
+    let point_ticks = viewer
+        .snapshots
+        .windows(2)
+        .filter(|pair| {
+            pair[0].score_left != pair[1].score_left
+                || pair[0].score_right != pair[1].score_right
+                || pair[0].lives != pair[1].lives
+        })
+        .map(|pair| pair[1].tick as f32)
+        .collect::<Vec<_>>();
+
+    let mut status_text = None;
+    let mut progress = None;
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::FlexStart,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: ui_assets.transparent.clone(),
+            ..Default::default()
+        })
+        .with(StateScoped(&[AppState::ReplayViewer]))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Px(TIMELINE_HEIGHT)),
+                        ..Default::default()
+                    },
+                    material: ui_assets.timeline.clone(),
+                    ..Default::default()
+                })
+                .with_children(|timeline| {
+                    progress = timeline
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: ui_assets.timeline_progress.clone(),
+                            ..Default::default()
+                        })
+                        .current_entity();
+
+                    for tick in &point_ticks {
+                        timeline.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: Rect {
+                                    left: Val::Percent(100.0 * tick / last_tick),
+                                    ..Default::default()
+                                },
+                                size: Size::new(Val::Px(3.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: ui_assets.timeline_marker.clone(),
+                            ..Default::default()
+                        });
+                    }
+                });
+
+            for (line, font_size) in &[
+                (String::new(), 20.0),
+                (
+                    "SPACE PLAY/PAUSE  LEFT/RIGHT STEP  UP/DOWN SPEED  0-9 SEEK  ESC EXIT"
+                        .to_string(),
+                    15.0,
+                ),
+            ] {
+                let entity = parent
+                    .spawn(TextBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(700.0), Val::Px(font_size * 1.5)),
+                            ..Default::default()
+                        },
+                        text: Text {
+                            value: line.clone(),
+                            font: ui_assets.font.clone(),
+                            style: TextStyle {
+                                font_size: *font_size,
+                                color: Color::rgb(1.0, 1.0, 1.0),
+                                alignment: TextAlignment {
+                                    vertical: VerticalAlign::Center,
+                                    horizontal: HorizontalAlign::Center,
+                                },
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .current_entity();
+                if status_text.is_none() {
+                    status_text = entity;
+                }
+            }
+        });
+
+    viewer.status_text = status_text;
+    viewer.progress = progress;
+}
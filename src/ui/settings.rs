@@ -0,0 +1,127 @@
+use crate::game::UiAssets;
+use crate::input::{is_bindable, key_label, Action, InputMap};
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+use simple_pong_bevy::simulation::Side;
+
+#[derive(Default)]
+pub struct SettingsState {
+    /// Index into `bindings()`.
+    selected: usize,
+    /// Waiting for the key to bind to the selected action.
+    rebinding: bool,
+    binding_texts: Vec<Entity>,
+    status_text: Option<Entity>,
+}
+
+/// Lists the controls of all players. Select an action with the arrow keys, press enter and
+/// then the new key, or escape to keep the old one. Leaving the screen saves the bindings.
+pub fn settings_system(
+    commands: &mut Commands,
+    mut settings: Local<SettingsState>,
+    mut state: ResMut<GameState>,
+    mut input_map: ResMut<InputMap>,
+    keyboard_input: Res<Input<KeyCode>>,
+    ui_assets: Res<UiAssets>,
+    mut text_query: Query<&mut Text>,
+) {
+    let bindings = bindings();
+
+    if state.just_entered(AppState::Settings) {
+        settings.selected = 0;
+        settings.rebinding = false;
+
+        let mut lines = vec![("CONTROLS".to_string(), 50.0)];
+        lines.extend(
+            (0..bindings.len()).map(|index| (binding_line(&settings, &input_map, index), 16.0)),
+        );
+        lines.push((conflict_line(&input_map), 20.0));
+        lines.push((
+            "UP/DOWN - SELECT  ENTER - REBIND  ESC - BACK".to_string(),
+            20.0,
+        ));
+
+        let entities = spawn_text_screen(commands, &ui_assets, &[AppState::Settings], &lines);
+        settings.binding_texts = entities[1..=bindings.len()].to_vec();
+        settings.status_text = entities.get(bindings.len() + 1).copied();
+        return;
+    }
+
+    if !state.is(AppState::Settings) {
+        return;
+    } else if settings.rebinding {
+        let key = match keyboard_input.get_just_pressed().next() {
+            Some(key) => *key,
+            None => return,
+        };
+        if key == KeyCode::Escape {
+            settings.rebinding = false;
+        } else if is_bindable(key) {
+            let (side, action) = bindings[settings.selected];
+            input_map.bind(side, action, key);
+            settings.rebinding = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        settings.selected = (settings.selected + bindings.len() - 1) % bindings.len();
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        settings.selected = (settings.selected + 1) % bindings.len();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        settings.rebinding = true;
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        if let Err(error) = input_map.save() {
+            eprintln!("Could not save controls: {}", error);
+        }
+        state.set(AppState::MainMenu);
+        return;
+    } else {
+        return;
+    }
+
+    for (index, entity) in settings.binding_texts.iter().enumerate() {
+        if let Ok(mut text) = text_query.get_mut(*entity) {
+            text.value = binding_line(&settings, &input_map, index);
+        }
+    }
+    if let Some(mut text) = settings
+        .status_text
+        .and_then(|entity| text_query.get_mut(entity).ok())
+    {
+        text.value = conflict_line(&input_map);
+    }
+}
+
+fn binding_line(settings: &SettingsState, input_map: &InputMap, index: usize) -> String {
+    let (side, action) = bindings()[index];
+    let key = if index == settings.selected && settings.rebinding {
+        "PRESS A KEY, ESC - CANCEL".to_string()
+    } else {
+        key_label(input_map.key(side, action))
+    };
+    let line = format!("{:?} {}: {}", side, action.label(side), key).to_uppercase();
+    if index == settings.selected {
+        format!("> {} <", line)
+    } else {
+        line
+    }
+}
+
+/// Names the first conflicting key, players have to fix them one after another anyway.
+fn conflict_line(input_map: &InputMap) -> String {
+    input_map
+        .conflicts()
+        .first()
+        .map(|conflict| conflict.to_string())
+        .unwrap_or_default()
+}
+
+/// Every action of every player, in the order they are listed on screen.
+fn bindings() -> Vec<(Side, Action)> {
+    Side::ALL
+        .iter()
+        .flat_map(|side| Action::ALL.iter().map(move |action| (*side, *action)))
+        .collect()
+}
@@ -0,0 +1,113 @@
+use crate::audio::{play_bounce_sound, AudioPlayer, Sounds};
+use crate::game::UiAssets;
+use crate::state::{AppState, GameState};
+use crate::ui::spawn_text_screen;
+use bevy::ecs::{Commands, Entity, Local, Query, Res, ResMut};
+use bevy::input::keyboard::KeyCode;
+use bevy::input::Input;
+use bevy::ui::widget::Text;
+use simple_pong_bevy::mixer::{Bus, Mixer};
+
+/// How much one press of left or right changes a volume.
+const VOLUME_STEP: f32 = 0.1;
+/// Characters of a full slider.
+const SLIDER_LENGTH: usize = 20;
+
+#[derive(Default)]
+pub struct VolumeScreen {
+    /// Index into `Bus::ALL`.
+    selected: usize,
+    slider_texts: Vec<Entity>,
+}
+
+/// One slider per volume bus. Select a bus with up and down, change its volume with left and
+/// right and mute it with `M`. Leaving the screen saves the volumes.
+pub fn volume_system(
+    commands: &mut Commands,
+    mut screen: Local<VolumeScreen>,
+    mut state: ResMut<GameState>,
+    mut mixer: ResMut<Mixer>,
+    player: Res<AudioPlayer>,
+    sounds: Res<Sounds>,
+    keyboard_input: Res<Input<KeyCode>>,
+    ui_assets: Res<UiAssets>,
+    mut text_query: Query<&mut Text>,
+) {
+    if state.just_entered(AppState::Volume) {
+        screen.selected = 0;
+        let mut lines = vec![("VOLUME".to_string(), 50.0)];
+        lines.extend(
+            (0..Bus::ALL.len()).map(|index| (slider_line(&mixer, index, screen.selected), 25.0)),
+        );
+        lines.push((
+            "UP/DOWN - SELECT  LEFT/RIGHT - ADJUST  M - MUTE  ESC - BACK".to_string(),
+            20.0,
+        ));
+        let entities = spawn_text_screen(commands, &ui_assets, &[AppState::Volume], &lines);
+        screen.slider_texts = entities[1..=Bus::ALL.len()].to_vec();
+        return;
+    }
+    if !state.is(AppState::Volume) {
+        return;
+    }
+
+    let bus = Bus::ALL[screen.selected];
+    let count = Bus::ALL.len();
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        screen.selected = (screen.selected + count - 1) % count;
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        screen.selected = (screen.selected + 1) % count;
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        let volume = mixer.volume(bus) - VOLUME_STEP;
+        mixer.set_volume(bus, volume);
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        let volume = mixer.volume(bus) + VOLUME_STEP;
+        mixer.set_volume(bus, volume);
+    } else if keyboard_input.just_pressed(KeyCode::M) {
+        let muted = mixer.is_muted(bus);
+        mixer.set_muted(bus, !muted);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        if let Err(error) = mixer.save() {
+            eprintln!("Could not save volumes: {}", error);
+        }
+        state.set(AppState::MainMenu);
+        return;
+    } else {
+        return;
+    }
+
+    // Let the new effects volume be heard, the music plays anyway.
+    let adjusted =
+        keyboard_input.just_pressed(KeyCode::Left) || keyboard_input.just_pressed(KeyCode::Right);
+    if adjusted && bus != Bus::Music {
+        play_bounce_sound(&player, &sounds, &mixer);
+    }
+
+    for (index, entity) in screen.slider_texts.iter().enumerate() {
+        if let Ok(mut text) = text_query.get_mut(*entity) {
+            text.value = slider_line(&mixer, index, screen.selected);
+        }
+    }
+}
+
+fn slider_line(mixer: &Mixer, index: usize, selected: usize) -> String {
+    let bus = Bus::ALL[index];
+    let filled = (mixer.volume(bus) * SLIDER_LENGTH as f32).round() as usize;
+    let level = if mixer.is_muted(bus) {
+        "MUTED".to_string()
+    } else {
+        format!("{}%", (mixer.volume(bus) * 100.0).round())
+    };
+    let line = format!(
+        "{} [{}{}] {}",
+        bus.name().to_uppercase(),
+        "#".repeat(filled),
+        "-".repeat(SLIDER_LENGTH - filled),
+        level
+    );
+    if index == selected {
+        format!("> {} <", line)
+    } else {
+        line
+    }
+}
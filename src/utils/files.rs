@@ -0,0 +1,29 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Resolves a path inside the `assets` folder the same way bevy's asset server does: relative
+/// to the crate while running through cargo, otherwise next to the executable.
+pub fn asset_path(path: &str) -> PathBuf {
+    let root = env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(PathBuf::from))
+        })
+        .unwrap_or_default();
+    root.join("assets").join(path)
+}
+
+/// Where user settings like `controls.ini` are stored: the platform's config directory, or the
+/// working directory if none can be found.
+pub fn config_path(file: &str) -> PathBuf {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    match config_dir {
+        Some(dir) => dir.join("simple-pong").join(file),
+        None => PathBuf::from(file),
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A minimal ini document: `[section]` headers followed by `key = value` lines. Lines starting
+/// with `#` or `;` are comments.
+#[derive(Default, Debug)]
+pub struct Ini {
+    sections: Vec<IniSection>,
+}
+
+#[derive(Debug)]
+pub struct IniSection {
+    pub name: String,
+    entries: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct IniError {
+    pub line: usize,
+    pub message: String,
+}
+impl fmt::Display for IniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Ini {
+    pub fn parse(source: &str) -> Result<Ini, IniError> {
+        let mut ini = Ini::default();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                ini.sections.push(IniSection {
+                    name: line[1..line.len() - 1].trim().to_string(),
+                    entries: Vec::new(),
+                });
+            } else if let Some(separator) = line.find('=') {
+                let section = ini.sections.last_mut().ok_or_else(|| IniError {
+                    line: index + 1,
+                    message: "entry outside of a section".to_string(),
+                })?;
+                section.entries.push((
+                    line[..separator].trim().to_string(),
+                    line[separator + 1..].trim().to_string(),
+                ));
+            } else {
+                return Err(IniError {
+                    line: index + 1,
+                    message: format!("expected `[section]` or `key = value`, got `{}`", line),
+                });
+            }
+        }
+        Ok(ini)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &IniSection> {
+        self.sections.iter()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&IniSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Returns the section with this name, appending an empty one if there is none yet.
+    pub fn section_mut(&mut self, name: &str) -> &mut IniSection {
+        match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => &mut self.sections[index],
+            None => {
+                self.sections.push(IniSection {
+                    name: name.to_string(),
+                    entries: Vec::new(),
+                });
+                self.sections.last_mut().unwrap()
+            }
+        }
+    }
+}
+impl fmt::Display for Ini {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, section) in self.sections.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", section.name)?;
+            for (key, value) in &section.entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IniSection {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of `key`, or adds it at the end of the section.
+    pub fn set(&mut self, key: &str, value: String) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    /// Reads and parses a mandatory value, the error names the section and key.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        let value = self
+            .get(key)
+            .ok_or_else(|| format!("[{}] is missing `{}`", self.name, key))?;
+        value
+            .parse()
+            .map_err(|_| format!("[{}] `{}` has invalid value `{}`", self.name, key, value))
+    }
+}
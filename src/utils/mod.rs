@@ -1,4 +1,11 @@
-mod camera;
-pub use self::camera::camera_virtual_screen_system;
-pub use self::camera::ScaleType;
-pub use self::camera::VirtualScreen;
+mod bytes;
+pub use self::bytes::{ByteReader, Truncated};
+
+mod files;
+pub use self::files::{asset_path, config_path};
+
+mod ini;
+pub use self::ini::{Ini, IniError, IniSection};
+
+mod rng;
+pub use self::rng::Rng;